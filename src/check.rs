@@ -3,9 +3,11 @@ use crate::error::Error;
 use crate::phenotype::load::load;
 
 pub(crate) fn check(config: &CheckConfig) -> Result<(), Error> {
-    let phenotypes = load(&config.phenotype_file)?;
-    for phenotype in phenotypes {
+    let definitions = load(&config.phenotype_file)?;
+    for phenotype in &definitions.phenotypes {
         println!("{}={}", phenotype.name, phenotype.sim);
     }
+    print!("{}", definitions.gen_correlations);
+    print!("{}", definitions.env_correlations);
     Ok(())
 }
\ No newline at end of file
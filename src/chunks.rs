@@ -0,0 +1,176 @@
+use std::fmt::{Display, Formatter};
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use fs_err::File;
+use noodles::bgzf;
+use noodles::bgzf::VirtualPosition;
+use crate::error::Error;
+use crate::record_reader::{detect_format, Format};
+use crate::plink;
+
+const BLOCK_HEADER_SIZE: usize = 12;
+const MAGIC: [u8; 4] = [31, 139, 8, 4];
+
+pub(crate) struct Chunk {
+    pub(crate) file: String,
+    pub(crate) i_chunk: usize,
+    pub(crate) n_chunks: usize,
+    pub(crate) seek_to: VirtualPosition,
+    pub(crate) start: VirtualPosition,
+    pub(crate) end: Option<VirtualPosition>,
+}
+
+impl Chunk {
+    pub(crate) fn whole(file: &str) -> Chunk {
+        let file = String::from(file);
+        let start = VirtualPosition::default();
+        Chunk { file, i_chunk: 0, n_chunks: 1, seek_to: start, start, end: None }
+    }
+}
+
+impl Display for Chunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.n_chunks > 1 {
+            write!(f, "{}, chunk {} of {}", self.file, self.i_chunk + 1, self.n_chunks)
+        } else {
+            write!(f, "{}", self.file)
+        }
+    }
+}
+
+pub(crate) fn split(file: &str, n_chunks: usize) -> Result<Vec<Chunk>, Error> {
+    if n_chunks < 2 || plink::is_plink(file) {
+        return Ok(vec![Chunk::whole(file)]);
+    }
+    let mut bgzf_reader = bgzf::Reader::new(File::open(file)?);
+    if detect_format(&mut bgzf_reader)? == Format::Bcf {
+        return Ok(vec![Chunk::whole(file)]);
+    }
+    let offsets = block_offsets(file)?;
+    if offsets.is_empty() {
+        return Ok(vec![Chunk::whole(file)]);
+    }
+    let mut i_blocks: Vec<usize> =
+        (0..n_chunks).map(|i_chunk| { i_chunk * offsets.len() / n_chunks }).collect();
+    i_blocks.dedup();
+    let n_chunks = i_blocks.len();
+    let chunks: Vec<Chunk> =
+        i_blocks.iter().enumerate().map(|(i_chunk, i_block)| {
+            let file = String::from(file);
+            let seek_to = to_virtual(offsets[i_block.saturating_sub(1)]);
+            let start = to_virtual(offsets[*i_block]);
+            let end = i_blocks.get(i_chunk + 1).map(|i_block| { to_virtual(offsets[*i_block]) });
+            Chunk { file, i_chunk, n_chunks, seek_to, start, end }
+        }).collect();
+    Ok(chunks)
+}
+
+fn to_virtual(offset: u64) -> VirtualPosition {
+    VirtualPosition::from(offset << 16)
+}
+
+fn block_offsets(file: &str) -> Result<Vec<u64>, Error> {
+    let mut reader = File::open(file)?;
+    let mut offsets: Vec<u64> = Vec::new();
+    let mut offset = 0u64;
+    let mut header = [0u8; BLOCK_HEADER_SIZE];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => { break }
+            Err(error) => { return Err(Error::from(error)) }
+        }
+        if header[0..4] != MAGIC {
+            return Err(Error::from(
+                format!("{} is not bgzipped: no BGZF block at offset {}.", file, offset)
+            ));
+        }
+        let extra_len = u16::from_le_bytes([header[10], header[11]]) as usize;
+        let mut extra = vec![0u8; extra_len];
+        reader.read_exact(&mut extra)?;
+        let block_size = block_size(&extra).ok_or_else(|| {
+            Error::from(format!("No BGZF block size at offset {} in {}.", offset, file))
+        })?;
+        offsets.push(offset);
+        offset += block_size;
+        reader.seek(SeekFrom::Start(offset))?;
+    }
+    Ok(offsets)
+}
+
+fn block_size(extra: &[u8]) -> Option<u64> {
+    let mut i = 0usize;
+    while i + 4 <= extra.len() {
+        let sub_len = u16::from_le_bytes([extra[i + 2], extra[i + 3]]) as usize;
+        if extra[i] == b'B' && extra[i + 1] == b'C' && sub_len == 2 && i + 6 <= extra.len() {
+            return Some(u16::from_le_bytes([extra[i + 4], extra[i + 5]]) as u64 + 1);
+        }
+        i += 4 + sub_len;
+    }
+    None
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+    use crate::test_util::temp_path;
+
+    pub(crate) fn write_bgzf(name: &str, blocks: &[String]) -> String {
+        let path = temp_path(name);
+        let mut writer = bgzf::Writer::new(std::fs::File::create(&path).unwrap());
+        for block in blocks {
+            writer.write_all(block.as_bytes()).unwrap();
+            writer.flush().unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn check_chunks_cover_file(chunks: &[Chunk]) {
+        let first = chunks.first().unwrap();
+        assert_eq!(first.start, VirtualPosition::default());
+        assert_eq!(first.seek_to, VirtualPosition::default());
+        assert!(chunks.last().unwrap().end.is_none());
+        for (i_chunk, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.i_chunk, i_chunk);
+            assert_eq!(chunk.n_chunks, chunks.len());
+            assert!(chunk.seek_to <= chunk.start);
+            if let Some(next) = chunks.get(i_chunk + 1) {
+                assert_eq!(chunk.end, Some(next.start));
+            }
+        }
+    }
+
+    #[test]
+    fn split_at_block_boundaries() {
+        let blocks: Vec<String> = (0..6).map(|i| { format!("block {}\n", i) }).collect();
+        let file = write_bgzf("split.vcf.gz", &blocks);
+        let n_blocks = block_offsets(&file).unwrap().len();
+        assert_eq!(n_blocks, blocks.len() + 1);
+        for n_chunks in 1..(2 * n_blocks) {
+            let chunks = split(&file, n_chunks).unwrap();
+            assert_eq!(chunks.len(), n_chunks.min(n_blocks));
+            check_chunks_cover_file(&chunks);
+        }
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn split_single_block() {
+        let file = write_bgzf("single.vcf.gz", &[String::from("only block\n")]);
+        for n_chunks in 1..5 {
+            let chunks = split(&file, n_chunks).unwrap();
+            assert!(chunks.len() <= 2);
+            check_chunks_cover_file(&chunks);
+        }
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn split_rejects_file_that_is_not_bgzipped() {
+        let file = temp_path("plain.vcf");
+        std::fs::write(&file, "##fileformat=VCFv4.2\n").unwrap();
+        assert!(block_offsets(&file).is_err());
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use fs_err::File;
+use crate::error::Error;
+use crate::locus::Locus;
+use crate::sim::genotype_sim::GenotypeSim;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Sex {
+    Male,
+    Female,
+    Unknown,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum CompensationMode {
+    None,
+    Full,
+    Escape,
+}
+
+pub(crate) enum Sexes {
+    ById(HashMap<String, Sex>),
+    ByIndex(Vec<Sex>),
+}
+
+pub(crate) struct Compensation {
+    modes: HashMap<String, CompensationMode>,
+    escapes: HashMap<String, Vec<(usize, usize)>>,
+    pub(crate) sexes: Option<Sexes>,
+}
+
+impl Compensation {
+    pub(crate) fn new(specs: &[String], escape_file: &Option<String>, sex_file: &Option<String>)
+                      -> Result<Option<Compensation>, Error> {
+        if specs.is_empty() {
+            return Ok(None);
+        }
+        let mut modes: HashMap<String, CompensationMode> = HashMap::new();
+        for spec in specs {
+            let (contig, mode) = spec.split_once('=').ok_or_else(|| {
+                Error::from(format!("Need contig=mode for dosage compensation, but got '{}'.",
+                                    spec))
+            })?;
+            modes.insert(String::from(contig), CompensationMode::parse(mode)?);
+        }
+        let needs_escapes =
+            modes.values().any(|mode| { *mode == CompensationMode::Escape });
+        let escapes = match escape_file {
+            Some(escape_file) => { load_escapes(escape_file)? }
+            None if needs_escapes => {
+                return Err(Error::from("Compensation mode escape needs an escape list."));
+            }
+            None => { HashMap::new() }
+        };
+        let sexes = sex_file.as_ref().map(|sex_file| { load_sexes(sex_file) }).transpose()?;
+        Ok(Some(Compensation { modes, escapes, sexes }))
+    }
+    pub(crate) fn covers(&self, contig: &str) -> bool {
+        self.modes.contains_key(contig)
+    }
+    pub(crate) fn sexes_for(&self, sample_ids: &[String]) -> Result<Vec<Sex>, Error> {
+        match &self.sexes {
+            None => { Err(Error::from("Sexes of samples are not known.")) }
+            Some(Sexes::ByIndex(sexes)) => {
+                if sexes.len() == sample_ids.len() {
+                    Ok(sexes.clone())
+                } else {
+                    Err(Error::from(
+                        format!("Inferred sex of {} samples, but have {} samples.",
+                                sexes.len(), sample_ids.len())
+                    ))
+                }
+            }
+            Some(Sexes::ById(sexes)) => {
+                sample_ids.iter().map(|sample_id| {
+                    sexes.get(sample_id).copied().ok_or_else(|| {
+                        Error::from(format!("No sex given for sample {}.", sample_id))
+                    })
+                }).collect()
+            }
+        }
+    }
+    pub(crate) fn report_uncompensated(&self, sample_ids: &[String]) -> Result<(), Error> {
+        let sexes = self.sexes_for(sample_ids)?;
+        let n_unknown = sexes.iter().filter(|sex| { **sex == Sex::Unknown }).count();
+        if n_unknown > 0 {
+            println!("Warning: {} of {} samples are of unknown sex and will not be compensated.",
+                     n_unknown, sexes.len());
+        }
+        Ok(())
+    }
+    fn is_full_at(&self, locus: &Locus) -> Option<bool> {
+        match self.modes.get(&locus.chrom)? {
+            CompensationMode::None => { Some(false) }
+            CompensationMode::Full => { Some(true) }
+            CompensationMode::Escape => { Some(!self.is_escaped(locus)) }
+        }
+    }
+    pub(crate) fn is_escaped(&self, locus: &Locus) -> bool {
+        if self.modes.get(&locus.chrom) != Some(&CompensationMode::Escape) {
+            return false;
+        }
+        self.escapes.get(&locus.chrom).map(|escapes| {
+            escapes.iter().any(|(start, end)| { *start <= locus.pos && locus.pos <= *end })
+        }).unwrap_or(false)
+    }
+    pub(crate) fn apply(&self, locus: &Locus, sexes: &[Sex],
+                        genotype_sims: &mut [Option<GenotypeSim>]) {
+        if let Some(full) = self.is_full_at(locus) {
+            for (genotype_sim, sex) in genotype_sims.iter_mut().zip(sexes.iter()) {
+                if let (Some(genotype_sim), Sex::Male) = (genotype_sim, sex) {
+                    genotype_sim.make_haploid(full);
+                }
+            }
+        }
+    }
+}
+
+impl CompensationMode {
+    fn parse(string: &str) -> Result<CompensationMode, Error> {
+        match string {
+            "none" => { Ok(CompensationMode::None) }
+            "full" => { Ok(CompensationMode::Full) }
+            "escape" => { Ok(CompensationMode::Escape) }
+            _ => {
+                Err(Error::from(
+                    format!("Compensation mode needs to be none, full or escape, but got '{}'.",
+                            string)
+                ))
+            }
+        }
+    }
+}
+
+impl Sex {
+    pub(crate) fn parse(string: &str) -> Result<Sex, Error> {
+        match string {
+            "M" | "m" | "male" | "1" => { Ok(Sex::Male) }
+            "F" | "f" | "female" | "2" => { Ok(Sex::Female) }
+            "NA" | "0" => { Ok(Sex::Unknown) }
+            _ => { Err(Error::from(format!("Cannot parse '{}' as sex.", string))) }
+        }
+    }
+    pub(crate) fn code(&self) -> char {
+        match self {
+            Sex::Male => { 'M' }
+            Sex::Female => { 'F' }
+            Sex::Unknown => { '0' }
+        }
+    }
+}
+
+fn data_lines(file: &str) -> Result<Vec<Vec<String>>, Error> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for line in BufReader::new(File::open(file)?).lines() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        rows.push(line.split('\t').map(String::from).collect());
+    }
+    Ok(rows)
+}
+
+fn load_escapes(file: &str) -> Result<HashMap<String, Vec<(usize, usize)>>, Error> {
+    let mut escapes: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+    for row in data_lines(file)? {
+        if row.len() < 3 {
+            return Err(Error::from(
+                format!("Expected chrom, start and end in {}, but got '{}'.", file,
+                        row.join("\t"))
+            ));
+        }
+        let start = row[1].parse::<usize>()?;
+        let end = row[2].parse::<usize>()?;
+        escapes.entry(row[0].clone()).or_default().push((start, end));
+    }
+    Ok(escapes)
+}
+
+fn load_sexes(file: &str) -> Result<Sexes, Error> {
+    let mut sexes: HashMap<String, Sex> = HashMap::new();
+    for row in data_lines(file)? {
+        if row.len() < 2 {
+            return Err(Error::from(
+                format!("Expected sample id and sex in {}, but got '{}'.", file,
+                        row.join("\t"))
+            ));
+        }
+        sexes.insert(row[0].clone(), Sex::parse(&row[1])?);
+    }
+    Ok(Sexes::ById(sexes))
+}
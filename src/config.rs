@@ -6,6 +6,7 @@ use std::num::ParseIntError;
 use crate::http::Range;
 use noodles::core::region::Region;
 use crate::region;
+use crate::sim::genotype_sim::DosageField;
 
 pub(crate) enum Config {
     Check(CheckConfig),
@@ -25,6 +26,13 @@ pub(crate) struct VcfConfig {
     pub(crate) inputs: Option<Vec<String>>,
     pub(crate) phenotype_file: String,
     pub(crate) output: String,
+    pub(crate) seed: Option<u64>,
+    pub(crate) effects_out: Option<String>,
+    pub(crate) compensation: Vec<String>,
+    pub(crate) escape_list: Option<String>,
+    pub(crate) sex_file: Option<String>,
+    pub(crate) dosage_field: DosageField,
+    pub(crate) threads: usize,
 }
 
 pub(crate) struct MergeConfig {
@@ -36,6 +44,8 @@ pub(crate) struct RenderConfig {
     pub(crate) inputs: Vec<String>,
     pub(crate) phenotype_file: String,
     pub(crate) output: String,
+    pub(crate) seed: Option<u64>,
+    pub(crate) covariates: Option<String>,
 }
 
 pub(crate) struct DownloadConfig {
@@ -57,6 +67,12 @@ pub(crate) struct GcsSampleConfig {
     pub(crate) region_size: usize,
     pub(crate) step_size_max: usize,
     pub(crate) output: String,
+    pub(crate) seed: Option<u64>,
+    pub(crate) effects_out: Option<String>,
+    pub(crate) compensation: Vec<String>,
+    pub(crate) escape_list: Option<String>,
+    pub(crate) sex_file: Option<String>,
+    pub(crate) dosage_field: DosageField,
 }
 
 const CHECK: &str = "check";
@@ -77,6 +93,14 @@ const INDEX: &str = "index";
 const RANGE: &str = "range";
 const REGION_SIZE: &str = "region-size";
 const STEP_SIZE_MAX: &str = "step-size-max";
+const SEED: &str = "seed";
+const EFFECTS_OUT: &str = "effects-out";
+const COVARIATES: &str = "covariates";
+const COMPENSATION: &str = "compensation";
+const ESCAPE_LIST: &str = "escape-list";
+const SEX: &str = "sex";
+const DOSAGE_FIELD: &str = "dosage-field";
+const THREADS: &str = "threads";
 
 fn subcommand_problem(problem: &str) -> Result<Config, Error> {
     let message =
@@ -107,7 +131,7 @@ pub(crate) fn get_config() -> Result<Config, Error> {
                     .takes_value(true)
                     .value_name("FILE")
                     .multiple_values(true)
-                    .help("Input files (VCF)")
+                    .help("Input files (VCF, BCF or PLINK .bed)")
                 )
                 .arg(Arg::new(PHENOTYPE)
                     .short('p')
@@ -123,6 +147,52 @@ pub(crate) fn get_config() -> Result<Config, Error> {
                     .value_name("FILE")
                     .help("Output file")
                 )
+                .arg(Arg::new(SEED)
+                    .short('s')
+                    .long(SEED)
+                    .takes_value(true)
+                    .value_name("SEED")
+                    .help("Seed for the random number generator")
+                )
+                .arg(Arg::new(EFFECTS_OUT)
+                    .short('e')
+                    .long(EFFECTS_OUT)
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Output file for allele effects (bgzipped if ending in .gz or .bgz)")
+                )
+                .arg(Arg::new(COMPENSATION)
+                    .long(COMPENSATION)
+                    .takes_value(true)
+                    .value_name("CONTIG=MODE")
+                    .multiple_values(true)
+                    .help("Dosage compensation mode (none, full or escape) for a contig")
+                )
+                .arg(Arg::new(ESCAPE_LIST)
+                    .long(ESCAPE_LIST)
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Regions escaping compensation (chrom, start, end)")
+                )
+                .arg(Arg::new(SEX)
+                    .long(SEX)
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Sex of samples (otherwise inferred from heterozygosity)")
+                )
+                .arg(Arg::new(DOSAGE_FIELD)
+                    .long(DOSAGE_FIELD)
+                    .takes_value(true)
+                    .value_name("FIELD")
+                    .help("FORMAT field for dosages: GT (default), DS or GP")
+                )
+                .arg(Arg::new(THREADS)
+                    .short('t')
+                    .long(THREADS)
+                    .takes_value(true)
+                    .value_name("N")
+                    .help("Number of threads to read input files and chunks of files with")
+                )
         )
         .subcommand(
             Command::new(MERGE)
@@ -167,6 +237,20 @@ pub(crate) fn get_config() -> Result<Config, Error> {
                 .value_name("FILE")
                 .help("Output file")
             )
+            .arg(Arg::new(SEED)
+                .short('s')
+                .long(SEED)
+                .takes_value(true)
+                .value_name("SEED")
+                .help("Seed for the random number generator")
+            )
+            .arg(Arg::new(COVARIATES)
+                .short('c')
+                .long(COVARIATES)
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Covariates file (otherwise age and sex are simulated)")
+            )
     ).subcommand(
         Command::new(DOWNLOAD)
             .arg_required_else_help(true)
@@ -206,14 +290,14 @@ pub(crate) fn get_config() -> Result<Config, Error> {
                 .long(DATA)
                 .takes_value(true)
                 .value_name("URL")
-                .help("URL to data file")
+                .help("URL or path of data file")
             )
             .arg(Arg::new(INDEX)
                 .short('i')
                 .long(INDEX)
                 .takes_value(true)
                 .value_name("URL")
-                .help("URL to index")
+                .help("URL or path of index")
             )
             .arg(Arg::new(RANGE)
                 .short('r')
@@ -230,14 +314,14 @@ pub(crate) fn get_config() -> Result<Config, Error> {
                 .long(DATA)
                 .takes_value(true)
                 .value_name("URL")
-                .help("URL to data file")
+                .help("URL or path of data file")
             )
             .arg(Arg::new(INDEX)
                 .short('i')
                 .long(INDEX)
                 .takes_value(true)
                 .value_name("URL")
-                .help("URL to index")
+                .help("URL or path of index")
             )
             .arg(Arg::new(PHENOTYPE)
                 .short('p')
@@ -267,6 +351,45 @@ pub(crate) fn get_config() -> Result<Config, Error> {
                 .value_name("FILE")
                 .help("Output file")
             )
+            .arg(Arg::new(SEED)
+                .short('s')
+                .long(SEED)
+                .takes_value(true)
+                .value_name("SEED")
+                .help("Seed for the random number generator")
+            )
+            .arg(Arg::new(EFFECTS_OUT)
+                .short('e')
+                .long(EFFECTS_OUT)
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Output file for allele effects (bgzipped if ending in .gz or .bgz)")
+            )
+            .arg(Arg::new(COMPENSATION)
+                .long(COMPENSATION)
+                .takes_value(true)
+                .value_name("CONTIG=MODE")
+                .multiple_values(true)
+                .help("Dosage compensation mode (none, full or escape) for a contig")
+            )
+            .arg(Arg::new(ESCAPE_LIST)
+                .long(ESCAPE_LIST)
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Regions escaping compensation (chrom, start, end)")
+            )
+            .arg(Arg::new(SEX)
+                .long(SEX)
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Sex of samples (otherwise inferred from heterozygosity)")
+            )
+            .arg(Arg::new(DOSAGE_FIELD)
+                .long(DOSAGE_FIELD)
+                .takes_value(true)
+                .value_name("FIELD")
+                .help("FORMAT field for dosages: GT (default), DS or GP")
+            )
     );
     let arg_matches = app.try_get_matches()?;
     match arg_matches.subcommand() {
@@ -288,7 +411,23 @@ pub(crate) fn get_config() -> Result<Config, Error> {
             let output =
                 String::from(error::none_to_error(vcf_matches.value_of(OUTPUT),
                                                   "Need to specify output file.")?);
-            Ok(Config::Vcf(VcfConfig { inputs, phenotype_file, output }))
+            let seed = parse_unpack::<u64, ParseIntError>(vcf_matches.value_of(SEED))?;
+            let effects_out = vcf_matches.value_of(EFFECTS_OUT).map(String::from);
+            let compensation = values_or_empty(vcf_matches.values_of(COMPENSATION));
+            let escape_list = vcf_matches.value_of(ESCAPE_LIST).map(String::from);
+            let sex_file = vcf_matches.value_of(SEX).map(String::from);
+            let dosage_field =
+                DosageField::parse(vcf_matches.value_of(DOSAGE_FIELD).unwrap_or("GT"))?;
+            let threads =
+                parse_unpack::<usize, ParseIntError>(vcf_matches.value_of(THREADS))?
+                    .unwrap_or(1);
+            if threads == 0 {
+                return Err(Error::from("Number of threads needs to be at least 1."));
+            }
+            Ok(Config::Vcf(VcfConfig {
+                inputs, phenotype_file, output, seed, effects_out, compensation, escape_list,
+                sex_file, dosage_field, threads,
+            }))
         }
         Some((MERGE, merge_matches)) => {
             let inputs =
@@ -312,7 +451,9 @@ pub(crate) fn get_config() -> Result<Config, Error> {
             let output =
                 String::from(error::none_to_error(render_matches.value_of(OUTPUT),
                                                   "Need to specify output file.")?);
-            Ok(Config::Render(RenderConfig { inputs, phenotype_file, output }))
+            let seed = parse_unpack::<u64, ParseIntError>(render_matches.value_of(SEED))?;
+            let covariates = render_matches.value_of(COVARIATES).map(String::from);
+            Ok(Config::Render(RenderConfig { inputs, phenotype_file, output, seed, covariates }))
         }
         Some((DOWNLOAD, download_matches)) => {
             let url =
@@ -333,9 +474,9 @@ pub(crate) fn get_config() -> Result<Config, Error> {
                 String::from(error::none_to_error(gcs_tabix_matches.value_of(DATA),
                                                   "Need to specify URL to data.")?);
             let index =
-                gcs_tabix_matches.value_of(DATA)
+                gcs_tabix_matches.value_of(INDEX)
                     .map(String::from)
-                    .unwrap_or(format!("{}.tbi", data));
+                    .unwrap_or_else(|| { default_index(&data) });
             let region =
                 region::parse(error::none_to_error(gcs_tabix_matches.value_of(RANGE),
                                                    "Need to specify range")?)?;
@@ -348,7 +489,7 @@ pub(crate) fn get_config() -> Result<Config, Error> {
             let index =
                 gcs_sample_matches.value_of(INDEX)
                     .map(String::from)
-                    .unwrap_or(format!("{}.tbi", data));
+                    .unwrap_or_else(|| { default_index(&data) });
             let phenotype_file =
                 String::from(
                     error::none_to_error(gcs_sample_matches.value_of(PHENOTYPE),
@@ -363,8 +504,19 @@ pub(crate) fn get_config() -> Result<Config, Error> {
             let output =
                 String::from(error::none_to_error(gcs_sample_matches.value_of(OUTPUT),
                                                   "Need to specify output file.")?);
+            let seed =
+                parse_unpack::<u64, ParseIntError>(gcs_sample_matches.value_of(SEED))?;
+            let effects_out = gcs_sample_matches.value_of(EFFECTS_OUT).map(String::from);
+            let compensation = values_or_empty(gcs_sample_matches.values_of(COMPENSATION));
+            let escape_list = gcs_sample_matches.value_of(ESCAPE_LIST).map(String::from);
+            let sex_file = gcs_sample_matches.value_of(SEX).map(String::from);
+            let dosage_field =
+                DosageField::parse(gcs_sample_matches.value_of(DOSAGE_FIELD).unwrap_or("GT"))?;
             Ok(Config::GcsSample(
-                GcsSampleConfig { data, index, phenotype_file, region_size, step_size_max, output }
+                GcsSampleConfig {
+                    data, index, phenotype_file, region_size, step_size_max, output, seed,
+                    effects_out, compensation, escape_list, sex_file, dosage_field,
+                }
             ))
         }
         Some(match_with_sub) => {
@@ -383,4 +535,16 @@ fn parse_unpack<T: FromStr, E: From<<T as FromStr>::Err>>(text: Option<&str>)
         None => { Ok(None) }
         Some(text) => { Ok(Some(text.parse::<T>()?)) }
     }
-}
\ No newline at end of file
+}
+
+fn values_or_empty(values: Option<clap::Values>) -> Vec<String> {
+    values.map(|values| { values.map(String::from).collect() }).unwrap_or_default()
+}
+
+fn default_index(data: &str) -> String {
+    if data.ends_with(".bcf") {
+        format!("{}.csi", data)
+    } else {
+        format!("{}.tbi", data)
+    }
+}
@@ -0,0 +1,184 @@
+use fs_err::File;
+use std::io::{BufWriter, Write};
+use noodles::bgzf;
+use crate::error::Error;
+use crate::locus::Allele;
+use crate::phenotype::Phenotype;
+use crate::phenotype::pheno_sim::GeneticModel;
+use crate::sim::allele_sim::AlleleSim;
+
+enum EffectsOutput {
+    Plain(BufWriter<File>),
+    Bgzf(bgzf::Writer<File>),
+}
+
+pub(crate) struct EffectsWriter {
+    file: String,
+    output: EffectsOutput,
+    dom_columns: Vec<usize>,
+    n_alleles: u64,
+    epistasis: Option<EpistasisWriter>,
+}
+
+struct EpistasisWriter {
+    file: String,
+    writer: BufWriter<File>,
+    n_pairs: u64,
+}
+
+fn is_bgzipped(file: &str) -> bool {
+    file.ends_with(".gz") || file.ends_with(".bgz")
+}
+
+fn epistasis_file_next_to(file: &str) -> String {
+    let file =
+        file.strip_suffix(".gz").or_else(|| { file.strip_suffix(".bgz") }).unwrap_or(file);
+    match file.strip_suffix(".tsv") {
+        None => { format!("{}.epistasis.tsv", file) }
+        Some(stem) => { format!("{}.epistasis.tsv", stem) }
+    }
+}
+
+impl EffectsOutput {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            EffectsOutput::Plain(writer) => { writer }
+            EffectsOutput::Bgzf(writer) => { writer }
+        }
+    }
+}
+
+impl EffectsWriter {
+    pub(crate) fn create(file: &str, phenotypes: &[Phenotype]) -> Result<EffectsWriter, Error> {
+        let output =
+            if is_bgzipped(file) {
+                EffectsOutput::Bgzf(bgzf::Writer::new(File::create(file)?))
+            } else {
+                EffectsOutput::Plain(BufWriter::new(File::create(file)?))
+            };
+        let file = String::from(file);
+        let dom_columns: Vec<usize> =
+            phenotypes.iter().enumerate().filter(|(_, phenotype)| {
+                !matches!(phenotype.sim.model, GeneticModel::Additive)
+            }).map(|(i_pheno, _)| { i_pheno }).collect();
+        let n_alleles = 0u64;
+        let mut columns: Vec<String> =
+            phenotypes.iter().map(|phenotype| { phenotype.name.clone() }).collect();
+        for i_pheno in &dom_columns {
+            columns.push(format!("{}:dom", phenotypes[*i_pheno].name));
+        }
+        let epistasis =
+            if phenotypes.iter().any(|phenotype| { phenotype.sim.epistasis.is_some() }) {
+                Some(EpistasisWriter::create(&epistasis_file_next_to(&file))?)
+            } else {
+                None
+            };
+        let mut effects_writer =
+            EffectsWriter { file, output, dom_columns, n_alleles, epistasis };
+        writeln!(effects_writer.output.writer(), "#chrom\tpos\tid\tref\talt\t{}",
+                 columns.join("\t"))?;
+        Ok(effects_writer)
+    }
+    pub(crate) fn write(&mut self, allele: &Allele, id: &str, allele_sim: &AlleleSim)
+                        -> Result<(), Error> {
+        if allele_sim.effects.iter().chain(allele_sim.dominance.iter())
+            .all(|value| { *value == 0.0 }) {
+            return Ok(());
+        }
+        let mut values: Vec<String> =
+            allele_sim.effects.iter().map(|effect| { effect.to_string() }).collect();
+        for i_pheno in &self.dom_columns {
+            values.push(allele_sim.dominance[*i_pheno].to_string());
+        }
+        let effects = values.join("\t");
+        writeln!(self.output.writer(), "{}\t{}\t{}\t{}\t{}\t{}", allele.locus.chrom,
+                 allele.locus.pos, id, allele.ref_bases, allele.alt, effects)?;
+        self.n_alleles += 1;
+        Ok(())
+    }
+    pub(crate) fn write_pair(&mut self, phenotype_name: &str, i_pair: usize, allele1: &Allele,
+                             allele2: &Allele, effect: f64) -> Result<(), Error> {
+        if let Some(epistasis) = &mut self.epistasis {
+            writeln!(epistasis.writer, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                     phenotype_name, i_pair, allele1.locus.chrom, allele1.locus.pos,
+                     allele1.ref_bases, allele1.alt, allele2.locus.chrom, allele2.locus.pos,
+                     allele2.ref_bases, allele2.alt, effect)?;
+            epistasis.n_pairs += 1;
+        }
+        Ok(())
+    }
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        match self.output {
+            EffectsOutput::Plain(mut writer) => { writer.flush()? }
+            EffectsOutput::Bgzf(writer) => { writer.finish()?; }
+        }
+        println!("Wrote effects of {} alleles to {}.", self.n_alleles, self.file);
+        if let Some(mut epistasis) = self.epistasis {
+            epistasis.writer.flush()?;
+            println!("Wrote effects of {} epistatic pairs to {}.", epistasis.n_pairs,
+                     epistasis.file);
+        }
+        Ok(())
+    }
+}
+
+impl EpistasisWriter {
+    fn create(file: &str) -> Result<EpistasisWriter, Error> {
+        let mut writer = BufWriter::new(File::create(file)?);
+        writeln!(writer, "#phenotype\tpair\tchrom1\tpos1\tref1\talt1\tchrom2\tpos2\tref2\talt2\t\
+        effect")?;
+        let file = String::from(file);
+        let n_pairs = 0u64;
+        Ok(EpistasisWriter { file, writer, n_pairs })
+    }
+}
+
+pub(crate) fn create_maybe(file: &Option<String>, phenotypes: &[Phenotype])
+                           -> Result<Option<EffectsWriter>, Error> {
+    file.as_ref().map(|file| { EffectsWriter::create(file, phenotypes) }).transpose()
+}
+
+pub(crate) fn finish_maybe(effects_writer: Option<EffectsWriter>) -> Result<(), Error> {
+    effects_writer.map(|effects_writer| { effects_writer.finish() }).transpose()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::locus::{Allele, Locus};
+    use crate::phenotype::{parse, Definition, Phenotype};
+    use crate::sim::allele_sim::AlleleSim;
+    use crate::test_util::temp_path;
+
+    fn parse_phenotypes(string: &str) -> Vec<Phenotype> {
+        match parse::parse(string).unwrap() {
+            Definition::Phenotypes(phenotypes) => { phenotypes }
+            _ => { panic!("Expected phenotypes.") }
+        }
+    }
+
+    #[test]
+    fn dominance_is_written_for_non_additive_models() {
+        let file = temp_path("effects.tsv");
+        let mut phenotypes = parse_phenotypes("foo=norm(0,1),0.3");
+        phenotypes.append(&mut parse_phenotypes("bar=norm(0,1),0.3,model=dev(norm(0,0.5))"));
+        let mut effects_writer = super::EffectsWriter::create(&file, &phenotypes).unwrap();
+        let sims = [(100, 0.5, 0.0, -0.25), (200, 0.0, 0.0, 0.0), (300, 0.0, 0.0, 0.75)];
+        for (pos, effect_foo, effect_bar, dominance_bar) in sims {
+            let allele = Allele::new(Locus::from_parts("chr1", pos), String::from("A"),
+                                     String::from("C"));
+            let allele_sim = AlleleSim {
+                effects: vec![effect_foo, effect_bar],
+                dominance: vec![0.0, dominance_bar],
+                epi_slots: Vec::new(),
+            };
+            effects_writer.write(&allele, ".", &allele_sim).unwrap();
+        }
+        effects_writer.finish().unwrap();
+        let content = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(content, "#chrom\tpos\tid\tref\talt\tfoo\tbar\tbar:dom\n\
+                             chr1\t100\t.\tA\tC\t0.5\t0\t-0.25\n\
+                             chr1\t300\t.\tA\tC\t0\t0\t0.75\n");
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -3,7 +3,7 @@ use std::io;
 use noodles::vcf;
 use noodles::vcf::record::genotypes::genotype::GenotypeError;
 use rand::distributions::WeightedError;
-use rand_distr::NormalError;
+use rand_distr::{NormalError, PoissonError, GammaError, ExpError};
 use std::num::{ParseIntError, ParseFloatError, TryFromIntError};
 use reqwest::header::ToStrError;
 
@@ -16,6 +16,9 @@ pub enum ErrorKind {
     Genotype,
     Weighted,
     Normal,
+    Poisson,
+    Gamma,
+    Exp,
     ParseInt,
     ParseFloat,
     Reqwest,
@@ -115,6 +118,24 @@ impl From<NormalError> for Error {
     }
 }
 
+impl From<PoissonError> for Error {
+    fn from(poisson_error: PoissonError) -> Self {
+        Error::from_error(ErrorKind::Poisson, &poisson_error)
+    }
+}
+
+impl From<GammaError> for Error {
+    fn from(gamma_error: GammaError) -> Self {
+        Error::from_error(ErrorKind::Gamma, &gamma_error)
+    }
+}
+
+impl From<ExpError> for Error {
+    fn from(exp_error: ExpError) -> Self {
+        Error::from_error(ErrorKind::Exp, &exp_error)
+    }
+}
+
 impl From<ParseIntError> for Error {
     fn from(parse_int_error: ParseIntError) -> Self {
         Error::from_error(ErrorKind::ParseInt, &parse_int_error)
@@ -167,6 +188,9 @@ impl ErrorKind {
             ErrorKind::Genotype => { "Genotype" }
             ErrorKind::Weighted => { "Weighted" }
             ErrorKind::Normal => { "Normal" }
+            ErrorKind::Poisson => { "Poisson" }
+            ErrorKind::Gamma => { "Gamma" }
+            ErrorKind::Exp => { "Exp" }
             ErrorKind::ParseInt => { "ParseInt"}
             ErrorKind::ParseFloat => { "ParseFloat" }
             ErrorKind::Reqwest => { "Reqwest" }
@@ -181,7 +205,7 @@ impl ErrorKind {
 
 impl Error {
     pub(crate) fn into_io_error(self) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, self)
+        io::Error::other(self)
     }
 }
 
@@ -124,7 +124,7 @@ impl Intake {
         })
     }
     fn build_request(url: &str, range: &Range) -> RequestBuilder {
-        let builder_base = reqwest::Client::new().get(&*url);
+        let builder_base = reqwest::Client::new().get(url);
         if !range.is_everything() {
             builder_base.header("Range", range.as_header())
         } else {
@@ -149,7 +149,7 @@ impl Read for GcsReader {
                     None => None,
                     Some(result) => {
                         Some(result.map_err(|reqwest_error| {
-                            io::Error::new(io::ErrorKind::Other, reqwest_error)
+                            io::Error::other(reqwest_error)
                         })?)
                     }
                 };
@@ -13,15 +13,11 @@ impl Range {
     }
     pub(crate) fn new_from(from: u64) -> Range { Range::new(Some(from), None) }
     pub(crate) fn is_everything(&self) -> bool {
-        match self {
-            Range { from: None, to: None } => { true }
-            Range { from: Some(0), to: None } => { true }
-            _ => { false }
-        }
+        matches!(self, Range { from: None | Some(0), to: None })
     }
     pub(crate) fn as_header(&self) -> String {
         let from = self.from.unwrap_or(0);
-        let to = self.to.map(|to| { to.to_string() }).unwrap_or_else(|| "".to_string());
+        let to = self.to.map(|to| { to.to_string() }).unwrap_or_default();
         format!("bytes={}-{}", from, to)
     }
 }
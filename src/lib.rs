@@ -21,6 +21,16 @@ mod region;
 mod tabix;
 mod region_iter;
 mod sample;
+mod seed;
+mod effects;
+mod compensation;
+mod chunks;
+mod source;
+mod vcf_index;
+mod record_reader;
+mod plink;
+#[cfg(test)]
+mod test_util;
 
 pub fn run() -> Result<(), Error> {
     let config = get_config()?;
@@ -1,9 +1,18 @@
 use noodles::vcf::record::{Chromosome, Position};
 use std::fmt::{Display, Formatter};
+use crate::seed;
 
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub(crate) struct Locus {
-    chrom: String,
-    pos: usize,
+    pub(crate) chrom: String,
+    pub(crate) pos: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Allele {
+    pub(crate) locus: Locus,
+    pub(crate) ref_bases: String,
+    pub(crate) alt: String,
 }
 
 impl Locus {
@@ -12,10 +21,30 @@ impl Locus {
         let pos = usize::from(*position);
         Locus { chrom, pos }
     }
+    pub(crate) fn from_parts(chrom: &str, pos: usize) -> Locus {
+        let chrom = String::from(chrom);
+        Locus { chrom, pos }
+    }
+}
+
+impl Allele {
+    pub(crate) fn new(locus: Locus, ref_bases: String, alt: String) -> Allele {
+        Allele { locus, ref_bases, alt }
+    }
+    pub(crate) fn derive_seed(&self, seed: u64) -> u64 {
+        seed::derive_seed(seed, &[&self.locus.chrom, &self.locus.pos.to_string(),
+            &self.ref_bases, &self.alt])
+    }
 }
 
 impl Display for Locus {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}:{}", self.chrom, self.pos)
     }
-}
\ No newline at end of file
+}
+
+impl Display for Allele {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}>{}", self.locus, self.ref_bases, self.alt)
+    }
+}
@@ -1,14 +1,28 @@
 use crate::phenotype::pheno_sim::PhenoSim;
+use crate::phenotype::correlation::{Correlation, Correlations};
 
 pub(crate) mod parse;
 pub(crate) mod pheno_sim;
 pub(crate) mod load;
+pub(crate) mod correlation;
 
 pub(crate) struct Phenotype {
     pub(crate) name: String,
     pub(crate) sim: PhenoSim,
 }
 
+pub(crate) enum Definition {
+    Phenotypes(Vec<Phenotype>),
+    GenCorrelation(Correlation),
+    EnvCorrelation(Correlation),
+}
+
+pub(crate) struct Definitions {
+    pub(crate) phenotypes: Vec<Phenotype>,
+    pub(crate) gen_correlations: Correlations,
+    pub(crate) env_correlations: Correlations,
+}
+
 impl Phenotype {
     pub(crate) fn new(name: String, sim: PhenoSim) -> Phenotype {
         Phenotype { name, sim }
@@ -0,0 +1,200 @@
+use crate::error::Error;
+use crate::phenotype::Phenotype;
+use std::fmt::{Display, Formatter};
+
+pub(crate) struct Correlation {
+    pub(crate) name1: String,
+    pub(crate) name2: String,
+    pub(crate) value: f64,
+}
+
+pub(crate) struct CorrelationGroup {
+    pub(crate) key: String,
+    pub(crate) i_phenos: Vec<usize>,
+    pub(crate) cholesky: Vec<Vec<f64>>,
+}
+
+pub(crate) struct Correlations {
+    pub(crate) prefix: &'static str,
+    pub(crate) correlations: Vec<Correlation>,
+    pub(crate) groups: Vec<CorrelationGroup>,
+}
+
+impl Correlation {
+    pub(crate) fn new(name1: String, name2: String, value: f64) -> Result<Correlation, Error> {
+        if !(value > -1.0 && value < 1.0) {
+            Err(Error::from(
+                format!("Correlation needs to be greater than -1.0 and less than 1.0, but is {}.",
+                        value)
+            ))
+        } else if name1 == name2 {
+            Err(Error::from(format!("Cannot correlate {} with itself.", name1)))
+        } else {
+            Ok(Correlation { name1, name2, value })
+        }
+    }
+}
+
+fn find_phenotype(phenotypes: &[Phenotype], name: &str) -> Result<usize, Error> {
+    phenotypes.iter().position(|phenotype| { phenotype.name == name }).ok_or_else(|| {
+        Error::from(format!("Correlation refers to unknown phenotype {}.", name))
+    })
+}
+
+fn find_root(roots: &mut [usize], i: usize) -> usize {
+    let mut i_root = i;
+    while roots[i_root] != i_root {
+        i_root = roots[i_root];
+    }
+    roots[i] = i_root;
+    i_root
+}
+
+fn cholesky(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut lower: Vec<Vec<f64>> = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| { lower[i][k] * lower[j][k] }).sum();
+            if i == j {
+                let diagonal = matrix[i][i] - sum;
+                if diagonal <= 0.0 {
+                    return None;
+                }
+                lower[i][j] = diagonal.sqrt();
+            } else {
+                lower[i][j] = (matrix[i][j] - sum) / lower[j][j];
+            }
+        }
+    }
+    Some(lower)
+}
+
+impl CorrelationGroup {
+    fn new(phenotypes: &[Phenotype], i_phenos: Vec<usize>, correlations: &[(usize, usize, f64)])
+           -> Result<CorrelationGroup, Error> {
+        let n = i_phenos.len();
+        let mut matrix: Vec<Vec<f64>> = vec![vec![0.0; n]; n];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        for (i_pheno1, i_pheno2, value) in correlations {
+            if let (Some(i), Some(j)) =
+            (i_phenos.iter().position(|i_pheno| { i_pheno == i_pheno1 }),
+             i_phenos.iter().position(|i_pheno| { i_pheno == i_pheno2 })) {
+                matrix[i][j] = *value;
+                matrix[j][i] = *value;
+            }
+        }
+        let key =
+            i_phenos.iter().map(|i_pheno| { phenotypes[*i_pheno].name.as_str() })
+                .collect::<Vec<&str>>().join(",");
+        let cholesky = cholesky(&matrix).ok_or_else(|| {
+            Error::from(format!("Correlations between {} are not positive definite.", key))
+        })?;
+        Ok(CorrelationGroup { key, i_phenos, cholesky })
+    }
+    pub(crate) fn correlate(&self, independent: &[f64]) -> Vec<f64> {
+        self.cholesky.iter().map(|row| {
+            row.iter().zip(independent.iter()).map(|(l, x)| { l * x }).sum()
+        }).collect()
+    }
+}
+
+impl Correlations {
+    pub(crate) fn new(prefix: &'static str, phenotypes: &[Phenotype],
+                      correlations: Vec<Correlation>) -> Result<Correlations, Error> {
+        let mut roots: Vec<usize> = (0..phenotypes.len()).collect();
+        let mut index_pairs: Vec<(usize, usize, f64)> = Vec::new();
+        for correlation in &correlations {
+            let i_pheno1 = find_phenotype(phenotypes, &correlation.name1)?;
+            let i_pheno2 = find_phenotype(phenotypes, &correlation.name2)?;
+            let root1 = find_root(&mut roots, i_pheno1);
+            let root2 = find_root(&mut roots, i_pheno2);
+            roots[root2] = root1;
+            index_pairs.push((i_pheno1, i_pheno2, correlation.value));
+        }
+        let mut groups: Vec<CorrelationGroup> = Vec::new();
+        let mut i_phenos_by_root: Vec<Vec<usize>> = vec![Vec::new(); phenotypes.len()];
+        for i_pheno in 0..phenotypes.len() {
+            let root = find_root(&mut roots, i_pheno);
+            i_phenos_by_root[root].push(i_pheno);
+        }
+        for i_phenos in i_phenos_by_root {
+            if i_phenos.len() > 1 {
+                groups.push(CorrelationGroup::new(phenotypes, i_phenos, &index_pairs)?);
+            }
+        }
+        Ok(Correlations { prefix, correlations, groups })
+    }
+    pub(crate) fn group_members(&self, n_phenotypes: usize) -> Vec<Option<(usize, usize)>> {
+        let mut group_members: Vec<Option<(usize, usize)>> = vec![None; n_phenotypes];
+        for (i_group, group) in self.groups.iter().enumerate() {
+            for (i_member, i_pheno) in group.i_phenos.iter().enumerate() {
+                group_members[*i_pheno] = Some((i_group, i_member));
+            }
+        }
+        group_members
+    }
+}
+
+impl Display for Correlations {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for correlation in &self.correlations {
+            writeln!(f, "{}({},{})={}", self.prefix, correlation.name1, correlation.name2,
+                     correlation.value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times_transpose(lower: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let n = lower.len();
+        (0..n).map(|i| {
+            (0..n).map(|j| { (0..n).map(|k| { lower[i][k] * lower[j][k] }).sum() }).collect()
+        }).collect()
+    }
+
+    #[test]
+    fn cholesky_reproduces_matrix() {
+        let matrix = vec![
+            vec![1.0, 0.4, -0.3, 0.1],
+            vec![0.4, 1.0, 0.2, 0.5],
+            vec![-0.3, 0.2, 1.0, 0.0],
+            vec![0.1, 0.5, 0.0, 1.0],
+        ];
+        let lower = cholesky(&matrix).unwrap();
+        for (i, row) in lower.iter().enumerate() {
+            assert!(row[i] > 0.0);
+            assert!(row[(i + 1)..].iter().all(|value| { *value == 0.0 }));
+        }
+        let product = times_transpose(&lower);
+        for (row, expected_row) in product.iter().zip(matrix.iter()) {
+            for (value, expected) in row.iter().zip(expected_row.iter()) {
+                assert!((value - expected).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn cholesky_rejects_matrix_that_is_not_positive_definite() {
+        let matrix = vec![
+            vec![1.0, 0.9, 0.9],
+            vec![0.9, 1.0, -0.9],
+            vec![0.9, -0.9, 1.0],
+        ];
+        assert!(cholesky(&matrix).is_none());
+    }
+
+    #[test]
+    fn correlation_needs_to_be_in_open_interval() {
+        for value in [-1.0, 1.0, 1.5, f64::NAN] {
+            assert!(Correlation::new(String::from("a"), String::from("b"), value).is_err());
+        }
+        assert!(Correlation::new(String::from("a"), String::from("b"), 0.99).is_ok());
+    }
+}
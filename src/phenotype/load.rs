@@ -1,16 +1,28 @@
 use crate::error::Error;
-use crate::phenotype::Phenotype;
+use crate::phenotype::{Definition, Definitions, Phenotype};
 use fs_err::File;
 use std::io::{BufReader, BufRead};
 use crate::phenotype::parse::parse;
+use crate::phenotype::correlation::{Correlation, Correlations};
 
-pub(crate) fn load(file: &str) -> Result<Vec<Phenotype>, Error> {
+pub(crate) const GEN_CORRELATION: &str = "rg";
+pub(crate) const ENV_CORRELATION: &str = "re";
+
+pub(crate) fn load(file: &str) -> Result<Definitions, Error> {
     let mut phenotypes: Vec<Phenotype> = Vec::new();
+    let mut gen_correlations: Vec<Correlation> = Vec::new();
+    let mut env_correlations: Vec<Correlation> = Vec::new();
     for line in BufReader::new(File::open(file)?).lines() {
         let line = line?;
-        phenotypes.append(&mut parse(&line)?)
-
+        match parse(&line)? {
+            Definition::Phenotypes(mut line_phenotypes) => {
+                phenotypes.append(&mut line_phenotypes)
+            }
+            Definition::GenCorrelation(correlation) => { gen_correlations.push(correlation) }
+            Definition::EnvCorrelation(correlation) => { env_correlations.push(correlation) }
+        }
     };
-    Ok(phenotypes)
+    let gen_correlations = Correlations::new(GEN_CORRELATION, &phenotypes, gen_correlations)?;
+    let env_correlations = Correlations::new(ENV_CORRELATION, &phenotypes, env_correlations)?;
+    Ok(Definitions { phenotypes, gen_correlations, env_correlations })
 }
-
@@ -2,7 +2,9 @@ mod tokenize;
 mod treeize;
 mod analyze;
 
-use crate::phenotype::Phenotype;
+use crate::phenotype::{Definition, Phenotype};
+use crate::phenotype::correlation::Correlation;
+use crate::phenotype::load::{ENV_CORRELATION, GEN_CORRELATION};
 use crate::error::Error;
 use std::fmt::{Display, Formatter};
 use crate::phenotype::pheno_sim::PhenoSim;
@@ -11,21 +13,29 @@ fn cannot_parse(string: &str, problem: &str) -> Error {
     Error::from(format!("Cannot parse {}: {}", string, problem))
 }
 
-pub(crate) fn parse(string: &str) -> Result<Vec<Phenotype>, Error> {
-    let mut split_by_eq = string.split('=');
-    let name_string = String::from(split_by_eq.next().ok_or_else(|| {
-        cannot_parse(string, "no '='")
-    })?);
-    let definition =
-        split_by_eq.next().ok_or_else(|| { cannot_parse(string, "no '='") })?;
-    if split_by_eq.next().is_some() {
-        return Err(cannot_parse(string, "More than one '='."));
+pub(crate) fn parse(string: &str) -> Result<Definition, Error> {
+    let (name_string, definition) =
+        string.split_once('=').ok_or_else(|| { cannot_parse(string, "no '='") })?;
+    if let Some((name1, name2)) = parse_pair(name_string, GEN_CORRELATION) {
+        let value = definition.parse::<f64>()?;
+        return Ok(Definition::GenCorrelation(Correlation::new(name1, name2, value)?));
     }
+    if let Some((name1, name2)) = parse_pair(name_string, ENV_CORRELATION) {
+        let value = definition.parse::<f64>()?;
+        return Ok(Definition::EnvCorrelation(Correlation::new(name1, name2, value)?));
+    }
+    let name_string = String::from(name_string);
     let sim = parse_sim(definition)?;
     let phenotypes =
         parse_names(&name_string)?.into_iter()
             .map(|name| { Phenotype::new(name, sim.clone()) }).collect();
-    Ok(phenotypes)
+    Ok(Definition::Phenotypes(phenotypes))
+}
+
+fn parse_pair(name_string: &str, prefix: &str) -> Option<(String, String)> {
+    let names = name_string.strip_prefix(prefix)?.strip_prefix('(')?.strip_suffix(')')?;
+    let (name1, name2) = names.split_once(',')?;
+    Some((String::from(name1.trim()), String::from(name2.trim())))
 }
 
 fn parse_names(name_string: &str) -> Result<Vec<String>, Error> {
@@ -51,7 +61,7 @@ fn parse_sim(definition: &str) -> Result<PhenoSim, Error> {
     Ok(sim)
 }
 
-pub(self) enum Value {
+enum Value {
     String(String),
     Number(f64),
 }
@@ -1,17 +1,28 @@
 use crate::phenotype::parse::treeize::{Call, Tree};
 use crate::error::Error;
-use crate::phenotype::pheno_sim::{PhenoSim, MyDistribution, Category, Binary};
+use crate::phenotype::pheno_sim::{PhenoSim, MyDistribution, Category, Binary, Effects,
+                                  CausalEffects, FixedEffects, BinaryMode, Ordinal,
+                                  Count, Survival, GeneticModel,
+                                  Epistasis, GxE, Exposure};
 use crate::phenotype::parse::Value;
 
 pub(super) fn analyze(call: Call) -> Result<PhenoSim, Error> {
-    if call.args.len() < 2 {
+    let mut args = Args::split(call)?;
+    let covariates = args.take_named("cov").map(number).transpose()?.unwrap_or(0.0);
+    let model =
+        args.take_named("model").map(genetic_model).transpose()?
+            .unwrap_or(GeneticModel::Additive);
+    let epistasis = args.take_named("epi").map(epistasis).transpose()?;
+    let gxe = args.take_named("gxe").map(gxe).transpose()?;
+    args.ensure_no_named()?;
+    if args.positional.len() < 2 {
         return Err(Error::from(
             format!("Phenotype definition needs as least two arguments, but got {}",
-                    call.args.len()))
+                    args.positional.len()))
         );
     }
-    let mut args_iter = call.args.into_iter();
-    let effect_distribution = distribution(args_iter.next().unwrap())?;
+    let mut args_iter = args.positional.into_iter();
+    let effects = effects(args_iter.next().unwrap())?;
     let heritability = number(args_iter.next().unwrap())?;
     if heritability <= 0.0 {
         return Err(Error::from(
@@ -24,10 +35,125 @@ pub(super) fn analyze(call: Call) -> Result<PhenoSim, Error> {
                     heritability)
         ))
     }
+    if covariates < 0.0 || heritability + covariates > 1.0 {
+        return Err(Error::from(
+            format!("Covariate fraction needs to be non-negative and no greater than 1.0 \
+            minus heritability, but is {}.", covariates)
+        ))
+    }
+    let epistasis_fraction =
+        epistasis.as_ref().map(|epistasis| { epistasis.fraction }).unwrap_or(0.0);
+    if heritability + covariates + epistasis_fraction > 1.0 {
+        return Err(Error::from(
+            format!("Epistatic fraction needs to be no greater than 1.0 minus heritability \
+            and covariate fraction, but is {}.", epistasis_fraction)
+        ))
+    }
+    if let Some(gxe) = &gxe {
+        let gxe_fractions = gxe.gxe_fraction + gxe.exposure_fraction;
+        if heritability + covariates + epistasis_fraction + gxe_fractions > 1.0 {
+            return Err(Error::from(
+                format!("GxE and exposure fractions together need to be no greater than 1.0 \
+                minus the other fractions, but are {}.", gxe_fractions)
+            ))
+        }
+    }
     let category =
         args_iter.next().map(|tree| { category(tree) }).transpose()?
             .unwrap_or(Category::Quantitative);
-    Ok(PhenoSim::new(effect_distribution, heritability, category))
+    Ok(PhenoSim::new(effects, heritability, category, covariates, model, epistasis, gxe))
+}
+
+fn gxe(tree: Tree) -> Result<GxE, Error> {
+    match tree {
+        Tree::Call(call) if call.name == "exposure" => {
+            if call.args.len() != 3 {
+                return Err(Error::from(format!("`exposure` takes three arguments, but got {}.",
+                                               call.args.len())));
+            }
+            let mut args_iter = call.args.into_iter();
+            let exposure = exposure(args_iter.next().unwrap())?;
+            let gxe_fraction = number(args_iter.next().unwrap())?;
+            let exposure_fraction = number(args_iter.next().unwrap())?;
+            GxE::new(exposure, gxe_fraction, exposure_fraction)
+        }
+        tree => {
+            Err(Error::from(format!("GxE needs to be `exposure(...)`, but got `{}`.", tree)))
+        }
+    }
+}
+
+fn exposure(tree: Tree) -> Result<Exposure, Error> {
+    match tree {
+        Tree::Value(Value::String(string)) if string == "norm" => { Ok(Exposure::Normal) }
+        Tree::Call(call) if call.name == "bin" && call.args.len() == 1 => {
+            Ok(Exposure::Binary(number(call.args.into_iter().next().unwrap())?))
+        }
+        tree => {
+            Err(Error::from(
+                format!("Exposure needs to be `bin(frequency)` or `norm`, but got `{}`.", tree)
+            ))
+        }
+    }
+}
+
+fn epistasis(tree: Tree) -> Result<Epistasis, Error> {
+    match tree {
+        Tree::Call(call) if call.name == "pairs" => {
+            if call.args.len() != 2 {
+                return Err(Error::from(format!("`pairs` takes two arguments, but got {}.",
+                                               call.args.len())));
+            }
+            let mut args_iter = call.args.into_iter();
+            let n_pairs = count(args_iter.next().unwrap())?;
+            let fraction = number(args_iter.next().unwrap())?;
+            Epistasis::new(n_pairs, fraction)
+        }
+        tree => {
+            Err(Error::from(format!("Epistasis needs to be `pairs(...)`, but got `{}`.", tree)))
+        }
+    }
+}
+
+fn genetic_model(tree: Tree) -> Result<GeneticModel, Error> {
+    match tree {
+        Tree::Value(Value::String(string)) if string == "add" => { Ok(GeneticModel::Additive) }
+        Tree::Value(Value::String(string)) if string == "dom" => { Ok(GeneticModel::Dominant) }
+        Tree::Value(Value::String(string)) if string == "rec" => { Ok(GeneticModel::Recessive) }
+        Tree::Call(call) if call.name == "dev" => {
+            if call.args.len() != 1 {
+                return Err(Error::from(format!("`dev` takes one argument, but got {}.",
+                                               call.args.len())));
+            }
+            let distribution = distribution(call.args.into_iter().next().unwrap())?;
+            Ok(GeneticModel::Deviation(distribution))
+        }
+        tree => {
+            Err(Error::from(
+                format!("Genetic model needs to be `add`, `dom`, `rec` or `dev(...)`, but got \
+                `{}`.", tree)
+            ))
+        }
+    }
+}
+
+fn effects(tree: Tree) -> Result<Effects, Error> {
+    match tree {
+        Tree::Call(call) if call.name == "causal" => { causal(call) }
+        Tree::Call(call) if call.name == "file" => { file(call) }
+        tree => { Ok(Effects::Random(distribution(tree)?)) }
+    }
+}
+
+fn causal(call: Call) -> Result<Effects, Error> {
+    if call.args.len() != 2 {
+        return Err(Error::from(format!("`causal` takes two arguments, but got {}.",
+                                       call.args.len())));
+    }
+    let mut args_iter = call.args.into_iter();
+    let n = count(args_iter.next().unwrap())?;
+    let distribution = distribution(args_iter.next().unwrap())?;
+    Ok(Effects::Causal(CausalEffects::new(n, distribution)?))
 }
 
 fn distribution(tree: Tree) -> Result<MyDistribution, Error> {
@@ -54,14 +180,17 @@ fn distribution(tree: Tree) -> Result<MyDistribution, Error> {
 }
 
 fn norm(call: Call) -> Result<MyDistribution, Error> {
-    if call.args.len() != 2 {
+    let mut args = Args::split(call)?;
+    let alpha = args.take_named("alpha").map(number).transpose()?;
+    args.ensure_no_named()?;
+    if args.positional.len() != 2 {
         return Err(Error::from(format!("`norm` takes two arguments, but got {}.",
-                                       call.args.len())));
+                                       args.positional.len())));
     }
-    let mut args_iter = call.args.into_iter();
+    let mut args_iter = args.positional.into_iter();
     let mean = number(args_iter.next().unwrap())?;
     let std_dev = number(args_iter.next().unwrap())?;
-    MyDistribution::new_normal(mean, std_dev)
+    MyDistribution::new_normal(mean, std_dev, alpha)
 }
 
 fn pick(call: Call) -> Result<MyDistribution, Error> {
@@ -91,13 +220,36 @@ fn number(tree: Tree) -> Result<f64, Error> {
     }
 }
 
+fn file(call: Call) -> Result<Effects, Error> {
+    if call.args.len() != 1 {
+        return Err(Error::from(format!("`file` takes one argument, but got {}.",
+                                       call.args.len())));
+    }
+    let file = format!("{}", value(call.args.into_iter().next().unwrap())?);
+    Ok(Effects::Fixed(FixedEffects::load(&file)?))
+}
+
+fn count(tree: Tree) -> Result<usize, Error> {
+    let number = number(tree)?;
+    if number < 0.0 || number.fract() != 0.0 {
+        Err(Error::from(format!("Need a non-negative integer, but got {}.", number)))
+    } else {
+        Ok(number as usize)
+    }
+}
+
 fn category(tree: Tree) -> Result<Category, Error> {
     match tree {
         Tree::Call(call) => {
             match call.name.as_str() {
                 "bin" => { bin(call) }
+                "ord" => { ord(call) }
+                "count" => { count_category(call) }
+                "surv" => { surv(call) }
                 _ => {
-                    Err(Error::from("The only category recognized is `bin`."))
+                    Err(Error::from(
+                        "The only categories recognized are `bin`, `ord`, `count` and `surv`."
+                    ))
                 }
             }
         }
@@ -108,10 +260,16 @@ fn category(tree: Tree) -> Result<Category, Error> {
 }
 
 fn bin(call: Call) -> Result<Category, Error> {
-    if call.args.is_empty() {
+    let mut args = Args::split(call)?;
+    let ascertainment = args.take_named("ascertain").map(number).transpose()?;
+    args.ensure_no_named()?;
+    if args.positional.is_empty() {
         return Err(Error::from("Bin needs as least one argument"));
     }
-    let mut args_iter = call.args.into_iter();
+    if args.positional.len() > 4 {
+        return Err(Error::from("Bin takes at most four arguments."));
+    }
+    let mut args_iter = args.positional.into_iter();
     let prevalence = number(args_iter.next().unwrap())?;
     let case =
         args_iter.next().map(|tree| { value(tree) }).transpose()?
@@ -119,7 +277,126 @@ fn bin(call: Call) -> Result<Category, Error> {
     let control =
         args_iter.next().map(|tree| { value(tree) }).transpose()?
             .map(|value| { format!("{}", value) }).unwrap_or_else(|| String::from("control"));
-    Ok(Category::Binary(Binary::new(prevalence, case, control)?))
+    let default_mode =
+        if ascertainment.is_some() { BinaryMode::Threshold } else { BinaryMode::Rank };
+    let mode =
+        args_iter.next().map(|tree| { binary_mode(tree) }).transpose()?
+            .unwrap_or(default_mode);
+    Ok(Category::Binary(Binary::new(prevalence, case, control, mode, ascertainment)?))
+}
+
+fn ord(call: Call) -> Result<Category, Error> {
+    if call.args.len() % 2 == 1 {
+        return Err(Error::from(
+            format!("`ord` takes an even number of arguments, but got {}.", call.args.len())
+        ));
+    }
+    let n_classes = call.args.len() / 2;
+    let mut args_iter = call.args.into_iter();
+    let mut proportions: Vec<f64> = Vec::new();
+    for _ in 0..n_classes {
+        proportions.push(number(args_iter.next().unwrap())?);
+    }
+    let mut labels: Vec<String> = Vec::new();
+    for tree in args_iter {
+        labels.push(format!("{}", value(tree)?));
+    }
+    Ok(Category::Ordinal(Ordinal::new(proportions, labels)?))
+}
+
+fn count_category(call: Call) -> Result<Category, Error> {
+    if call.args.len() < 2 || call.args.len() > 3 {
+        return Err(Error::from(format!("`count` takes two or three arguments, but got {}.",
+                                       call.args.len())));
+    }
+    let mut args_iter = call.args.into_iter();
+    let mean = number(args_iter.next().unwrap())?;
+    let scale = number(args_iter.next().unwrap())?;
+    let size = args_iter.next().map(number).transpose()?;
+    Ok(Category::Count(Count::new(mean, scale, size)?))
+}
+
+fn surv(call: Call) -> Result<Category, Error> {
+    let mut args = Args::split(call)?;
+    let admin_censoring = args.take_named("admin").map(number).transpose()?;
+    let random_censoring = args.take_named("rand").map(number).transpose()?;
+    args.ensure_no_named()?;
+    if args.positional.len() != 3 {
+        return Err(Error::from(format!("`surv` takes three arguments, but got {}.",
+                                       args.positional.len())));
+    }
+    let mut args_iter = args.positional.into_iter();
+    let rate = number(args_iter.next().unwrap())?;
+    let shape = number(args_iter.next().unwrap())?;
+    let effect = number(args_iter.next().unwrap())?;
+    let survival = Survival::new(rate, shape, effect, admin_censoring, random_censoring)?;
+    Ok(Category::Survival(survival))
+}
+
+fn binary_mode(tree: Tree) -> Result<BinaryMode, Error> {
+    match value(tree)? {
+        Value::String(string) if string == "rank" => { Ok(BinaryMode::Rank) }
+        Value::String(string) if string == "threshold" => { Ok(BinaryMode::Threshold) }
+        value => {
+            Err(Error::from(
+                format!("Binary mode needs to be `rank` or `threshold`, but got `{}`.", value)
+            ))
+        }
+    }
+}
+
+struct Args {
+    name: String,
+    positional: Vec<Tree>,
+    named: Vec<(String, Tree)>,
+}
+
+impl Args {
+    fn split(call: Call) -> Result<Args, Error> {
+        let name = call.name;
+        let mut positional: Vec<Tree> = Vec::new();
+        let mut named: Vec<(String, Tree)> = Vec::new();
+        for arg in call.args {
+            match arg {
+                Tree::Value(Value::String(string)) if string.contains('=') => {
+                    let (key, value) = string.split_once('=').unwrap();
+                    let value = match value.parse::<f64>() {
+                        Ok(number) => { Value::Number(number) }
+                        Err(_) => { Value::String(String::from(value)) }
+                    };
+                    named.push((String::from(key), Tree::Value(value)));
+                }
+                Tree::Call(Call { name: call_name, args }) if call_name.contains('=') => {
+                    let (key, call_name) = call_name.split_once('=').unwrap();
+                    let call = Call { name: String::from(call_name), args };
+                    named.push((String::from(key), Tree::Call(call)));
+                }
+                arg => {
+                    if !named.is_empty() {
+                        return Err(Error::from(
+                            format!("In `{}`, named arguments need to come last.", name)
+                        ));
+                    }
+                    positional.push(arg)
+                }
+            }
+        }
+        Ok(Args { name, positional, named })
+    }
+    fn take_named(&mut self, key: &str) -> Option<Tree> {
+        let i = self.named.iter().position(|(name, _)| { name == key })?;
+        Some(self.named.remove(i).1)
+    }
+    fn ensure_no_named(&self) -> Result<(), Error> {
+        match self.named.first() {
+            None => { Ok(()) }
+            Some((key, _)) => {
+                Err(Error::from(
+                    format!("`{}` does not take an argument named `{}`.", self.name, key)
+                ))
+            }
+        }
+    }
 }
 
 fn value(tree: Tree) -> Result<Value, Error> {
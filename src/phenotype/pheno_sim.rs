@@ -3,29 +3,117 @@ use rand_distr::Normal;
 use rand::Rng;
 use crate::error::Error;
 use std::fmt::{Display, Formatter};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::io::{BufRead, BufReader};
+use fs_err::File;
+use crate::locus::{Allele, Locus};
 
 #[derive(Clone)]
 pub(crate) struct PhenoSim {
-    pub(crate) effect_distribution: MyDistribution,
+    pub(crate) effects: Effects,
     pub(crate) heritability: f64,
     pub(crate) category: Category,
+    pub(crate) covariates: f64,
+    pub(crate) model: GeneticModel,
+    pub(crate) epistasis: Option<Epistasis>,
+    pub(crate) gxe: Option<GxE>,
+}
+
+#[derive(Clone)]
+pub(crate) struct GxE {
+    pub(crate) exposure: Exposure,
+    pub(crate) gxe_fraction: f64,
+    pub(crate) exposure_fraction: f64,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Exposure {
+    Binary(f64),
+    Normal,
+}
+
+#[derive(Clone)]
+pub(crate) struct Epistasis {
+    pub(crate) n_pairs: usize,
+    pub(crate) fraction: f64,
+}
+
+#[derive(Clone)]
+pub(crate) enum GeneticModel {
+    Additive,
+    Dominant,
+    Recessive,
+    Deviation(MyDistribution),
+}
+
+#[derive(Clone)]
+pub(crate) enum Effects {
+    Random(MyDistribution),
+    Causal(CausalEffects),
+    Fixed(FixedEffects),
+}
+
+#[derive(Clone)]
+pub(crate) struct CausalEffects {
+    pub(crate) n: usize,
+    pub(crate) distribution: MyDistribution,
+}
+
+#[derive(Clone)]
+pub(crate) struct FixedEffects {
+    pub(crate) file: String,
+    pub(crate) effects: Arc<HashMap<Allele, f64>>,
 }
 
 pub(crate) enum Category {
     Quantitative,
     Binary(Binary),
+    Ordinal(Ordinal),
+    Count(Count),
+    Survival(Survival),
 }
 
 pub(crate) struct Binary {
     pub(crate) prevalence: f64,
     pub(crate) case: String,
     pub(crate) control: String,
+    pub(crate) mode: BinaryMode,
+    pub(crate) ascertainment: Option<f64>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Ordinal {
+    pub(crate) proportions: Vec<f64>,
+    pub(crate) labels: Vec<String>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Count {
+    pub(crate) mean: f64,
+    pub(crate) scale: f64,
+    pub(crate) size: Option<f64>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Survival {
+    pub(crate) rate: f64,
+    pub(crate) shape: f64,
+    pub(crate) effect: f64,
+    pub(crate) admin_censoring: Option<f64>,
+    pub(crate) random_censoring: Option<f64>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum BinaryMode {
+    Rank,
+    Threshold,
 }
 
 #[derive(Clone)]
 pub(crate) enum MyDistribution {
     Stuck(StuckDistribution),
-    Norm(Normal<f64>),
+    Norm(NormDistribution),
     Pick(PickDistribution),
 }
 
@@ -34,26 +122,194 @@ pub(crate) struct StuckDistribution {
     value: f64,
 }
 
+#[derive(Clone)]
+pub(crate) struct NormDistribution {
+    normal: Normal<f64>,
+    alpha: Option<f64>,
+}
+
 pub(crate) struct PickDistribution {
     index_distribution: WeightedIndex<f64>,
     distributions: Vec<MyDistribution>,
 }
 
 impl PhenoSim {
-    pub(crate) fn new(effect_distribution: MyDistribution, heritability: f64, category: Category)
+    pub(crate) fn new(effects: Effects, heritability: f64, category: Category, covariates: f64,
+                      model: GeneticModel, epistasis: Option<Epistasis>, gxe: Option<GxE>)
                       -> PhenoSim {
-        PhenoSim { effect_distribution, heritability, category }
+        PhenoSim { effects, heritability, category, covariates, model, epistasis, gxe }
+    }
+    pub(crate) fn epistasis_fraction(&self) -> f64 {
+        self.epistasis.as_ref().map(|epistasis| { epistasis.fraction }).unwrap_or(0.0)
+    }
+    pub(crate) fn gxe_fractions(&self) -> f64 {
+        self.gxe.as_ref().map(|gxe| { gxe.gxe_fraction + gxe.exposure_fraction })
+            .unwrap_or(0.0)
+    }
+}
+
+impl CausalEffects {
+    pub(crate) fn new(n: usize, distribution: MyDistribution) -> Result<CausalEffects, Error> {
+        if n == 0 {
+            Err(Error::from("Number of causal variants needs to be at least one."))
+        } else {
+            Ok(CausalEffects { n, distribution })
+        }
+    }
+}
+
+impl FixedEffects {
+    pub(crate) fn load(file: &str) -> Result<FixedEffects, Error> {
+        let mut effects: HashMap<Allele, f64> = HashMap::new();
+        for line in BufReader::new(File::open(file)?).lines() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() != 5 {
+                return Err(Error::from(
+                    format!("Expected chrom, pos, ref, alt and effect in {}, but got '{}'.",
+                            file, line)
+                ));
+            }
+            let locus = Locus::from_parts(parts[0], parts[1].parse::<usize>()?);
+            let allele = Allele::new(locus, String::from(parts[2]), String::from(parts[3]));
+            let effect = parts[4].parse::<f64>()?;
+            effects.insert(allele, effect);
+        }
+        let file = String::from(file);
+        let effects = Arc::new(effects);
+        Ok(FixedEffects { file, effects })
     }
 }
 
 impl Binary {
-    pub(crate) fn new(prevalence: f64, case: String, control: String) -> Result<Binary, Error> {
+    pub(crate) fn new(prevalence: f64, case: String, control: String, mode: BinaryMode,
+                      ascertainment: Option<f64>) -> Result<Binary, Error> {
         if !(0.0..=1.0).contains(&prevalence) {
             Err(Error::from(
                 format!("Prevalence needs to be between 0.0 and 1.0, but is {}.", prevalence)
             ))
+        } else if ascertainment.map(|fraction| { fraction <= 0.0 || fraction >= 1.0 })
+            .unwrap_or(false) {
+            Err(Error::from(
+                format!("Ascertainment needs to be greater than 0.0 and less than 1.0, but is {}.",
+                        ascertainment.unwrap())
+            ))
+        } else {
+            Ok(Binary { prevalence, case, control, mode, ascertainment })
+        }
+    }
+}
+
+impl Ordinal {
+    pub(crate) fn new(proportions: Vec<f64>, labels: Vec<String>) -> Result<Ordinal, Error> {
+        if proportions.len() < 2 {
+            return Err(Error::from(
+                format!("Ordinal needs at least two classes, but got {}.", proportions.len())
+            ));
+        }
+        if proportions.len() != labels.len() {
+            return Err(Error::from(
+                format!("Ordinal needs as many labels as proportions, but got {} proportions \
+                and {} labels.", proportions.len(), labels.len())
+            ));
+        }
+        if let Some(proportion) = proportions.iter().find(|proportion| { **proportion < 0.0 }) {
+            return Err(Error::from(
+                format!("Proportions need to be non-negative, but got {}.", proportion)
+            ));
+        }
+        let sum: f64 = proportions.iter().sum();
+        if (sum - 1.0).abs() > 1e-6 {
+            return Err(Error::from(format!("Proportions need to add up to 1.0, but add up to {}.",
+                                           sum)));
+        }
+        Ok(Ordinal { proportions, labels })
+    }
+}
+
+impl Epistasis {
+    pub(crate) fn new(n_pairs: usize, fraction: f64) -> Result<Epistasis, Error> {
+        if n_pairs == 0 {
+            Err(Error::from("Number of epistatic pairs needs to be at least one."))
+        } else if fraction <= 0.0 {
+            Err(Error::from(
+                format!("Epistatic variance fraction needs to be greater than 0.0, but is {}.",
+                        fraction)
+            ))
+        } else {
+            Ok(Epistasis { n_pairs, fraction })
+        }
+    }
+}
+
+impl GxE {
+    pub(crate) fn new(exposure: Exposure, gxe_fraction: f64, exposure_fraction: f64)
+                      -> Result<GxE, Error> {
+        if let Exposure::Binary(p) = exposure {
+            if p <= 0.0 || p >= 1.0 {
+                return Err(Error::from(
+                    format!("Exposure frequency needs to be between 0.0 and 1.0, but is {}.",
+                            p)
+                ));
+            }
+        }
+        if gxe_fraction < 0.0 || exposure_fraction < 0.0 {
+            Err(Error::from("Variance fractions of GxE and exposure need to be non-negative."))
+        } else {
+            Ok(GxE { exposure, gxe_fraction, exposure_fraction })
+        }
+    }
+}
+
+impl Count {
+    pub(crate) fn new(mean: f64, scale: f64, size: Option<f64>) -> Result<Count, Error> {
+        if mean <= 0.0 {
+            Err(Error::from(format!("Mean count needs to be greater than 0.0, but is {}.", mean)))
+        } else if scale < 0.0 {
+            Err(Error::from(format!("Scale needs to be non-negative, but is {}.", scale)))
+        } else if size.map(|size| { size <= 0.0 }).unwrap_or(false) {
+            Err(Error::from(format!("Size needs to be greater than 0.0, but is {}.",
+                                    size.unwrap())))
         } else {
-            Ok(Binary { prevalence, case, control })
+            Ok(Count { mean, scale, size })
+        }
+    }
+    pub(crate) fn rate_for(&self, standardized_liability: f64) -> f64 {
+        self.mean * (self.scale * standardized_liability - self.scale * self.scale / 2.0).exp()
+    }
+}
+
+impl Survival {
+    pub(crate) fn new(rate: f64, shape: f64, effect: f64, admin_censoring: Option<f64>,
+                      random_censoring: Option<f64>) -> Result<Survival, Error> {
+        if rate <= 0.0 {
+            Err(Error::from(format!("Rate needs to be greater than 0.0, but is {}.", rate)))
+        } else if shape <= 0.0 {
+            Err(Error::from(format!("Shape needs to be greater than 0.0, but is {}.", shape)))
+        } else if admin_censoring.map(|time| { time <= 0.0 }).unwrap_or(false) {
+            Err(Error::from(format!("Censoring time needs to be greater than 0.0, but is {}.",
+                                    admin_censoring.unwrap())))
+        } else if random_censoring.map(|rate| { rate <= 0.0 }).unwrap_or(false) {
+            Err(Error::from(format!("Censoring rate needs to be greater than 0.0, but is {}.",
+                                    random_censoring.unwrap())))
+        } else {
+            Ok(Survival { rate, shape, effect, admin_censoring, random_censoring })
+        }
+    }
+    pub(crate) fn event_time(&self, standardized_liability: f64, uniform: f64) -> f64 {
+        let hazard_ratio = (self.effect * standardized_liability).exp();
+        (-uniform.ln() / (self.rate * hazard_ratio)).powf(1.0 / self.shape)
+    }
+}
+
+impl Category {
+    pub(crate) fn column_names(&self, name: &str) -> Vec<String> {
+        match self {
+            Category::Survival(_) => { vec![format!("{}_time", name), format!("{}_event", name)] }
+            _ => { vec![String::from(name)] }
         }
     }
 }
@@ -70,6 +326,29 @@ impl Distribution<f64> for StuckDistribution {
     }
 }
 
+impl NormDistribution {
+    pub(crate) fn new(mean: f64, std_dev: f64, alpha: Option<f64>)
+                      -> Result<NormDistribution, Error> {
+        let normal = Normal::new(mean, std_dev)?;
+        Ok(NormDistribution { normal, alpha })
+    }
+    fn scale_for(&self, freq: f64) -> f64 {
+        match self.alpha {
+            None => { 1.0 }
+            Some(alpha) => {
+                let heterozygosity = 2.0 * freq * (1.0 - freq);
+                if heterozygosity > 0.0 { heterozygosity.powf(alpha) } else { 0.0 }
+            }
+        }
+    }
+}
+
+impl Distribution<f64> for NormDistribution {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        self.normal.sample(rng)
+    }
+}
+
 impl PickDistribution {
     pub(crate) fn new(weights: Vec<f64>, distributions: Vec<MyDistribution>)
                       -> Result<PickDistribution, Error> {
@@ -88,13 +367,31 @@ impl MyDistribution {
     pub(crate) fn new_stuck(value: f64) -> MyDistribution {
         MyDistribution::Stuck(StuckDistribution::new(value))
     }
-    pub(crate) fn new_normal(mean: f64, std_dev: f64) -> Result<MyDistribution, Error> {
-        Ok(MyDistribution::Norm(Normal::new(mean, std_dev)?))
+    pub(crate) fn new_normal(mean: f64, std_dev: f64, alpha: Option<f64>)
+                             -> Result<MyDistribution, Error> {
+        Ok(MyDistribution::Norm(NormDistribution::new(mean, std_dev, alpha)?))
     }
     pub(crate) fn new_pick(weights: Vec<f64>, distributions: Vec<MyDistribution>)
                            -> Result<MyDistribution, Error> {
         Ok(MyDistribution::Pick(PickDistribution::new(weights, distributions)?))
     }
+    pub(crate) fn sample_effect<R: Rng + ?Sized>(&self, rng: &mut R, freq: f64,
+                                                 normal: Option<f64>) -> f64 {
+        match self {
+            MyDistribution::Stuck(stuck) => { stuck.sample(rng) }
+            MyDistribution::Norm(norm) => {
+                let effect = match normal {
+                    None => { norm.sample(rng) }
+                    Some(normal) => { norm.normal.mean() + norm.normal.std_dev() * normal }
+                };
+                effect * norm.scale_for(freq)
+            }
+            MyDistribution::Pick(pick) => {
+                let distribution = &pick.distributions[pick.index_distribution.sample(rng)];
+                distribution.sample_effect(rng, freq, normal)
+            }
+        }
+    }
 }
 
 impl Distribution<f64> for MyDistribution {
@@ -112,7 +409,9 @@ impl Clone for Binary {
         let prevalence = self.prevalence;
         let case = self.case.clone();
         let control = self.control.clone();
-        Binary { prevalence, case, control }
+        let mode = self.mode;
+        let ascertainment = self.ascertainment;
+        Binary { prevalence, case, control, mode, ascertainment }
     }
 }
 
@@ -121,6 +420,9 @@ impl Clone for Category {
         match self {
             Category::Quantitative => { Category::Quantitative }
             Category::Binary(binary) => { Category::Binary(binary.clone()) }
+            Category::Ordinal(ordinal) => { Category::Ordinal(ordinal.clone()) }
+            Category::Count(count) => { Category::Count(count.clone()) }
+            Category::Survival(survival) => { Category::Survival(survival.clone()) }
         }
     }
 }
@@ -135,14 +437,100 @@ impl Clone for PickDistribution {
 
 impl Display for PhenoSim {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.effects, self.heritability)?;
         match &self.category {
-            Category::Quantitative => {
-                write!(f, "{},{}", self.effect_distribution, self.heritability)
+            Category::Quantitative => {}
+            Category::Binary(binary) => { write!(f, ",{}", binary)?; }
+            Category::Ordinal(ordinal) => { write!(f, ",{}", ordinal)?; }
+            Category::Count(count) => { write!(f, ",{}", count)?; }
+            Category::Survival(survival) => { write!(f, ",{}", survival)?; }
+        }
+        if self.covariates > 0.0 {
+            write!(f, ",cov={}", self.covariates)?;
+        }
+        match &self.model {
+            GeneticModel::Additive => {}
+            GeneticModel::Dominant => { write!(f, ",model=dom")?; }
+            GeneticModel::Recessive => { write!(f, ",model=rec")?; }
+            GeneticModel::Deviation(distribution) => {
+                write!(f, ",model=dev({})", distribution)?;
             }
-            Category::Binary(Binary { prevalence, case, control }) => {
-                write!(f, "{},{},bin({},{},{})", self.effect_distribution, self.heritability,
-                       prevalence, case, control)
+        }
+        if let Some(epistasis) = &self.epistasis {
+            write!(f, ",epi=pairs({},{})", epistasis.n_pairs, epistasis.fraction)?;
+        }
+        if let Some(gxe) = &self.gxe {
+            write!(f, ",gxe=exposure({},{},{})", gxe.exposure, gxe.gxe_fraction,
+                   gxe.exposure_fraction)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Binary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bin({},{},{}", self.prevalence, self.case, self.control)?;
+        match (self.mode, self.ascertainment) {
+            (BinaryMode::Rank, None) => {}
+            (BinaryMode::Rank, Some(ascertainment)) => {
+                write!(f, ",rank,ascertain={}", ascertainment)?;
             }
+            (BinaryMode::Threshold, None) => { write!(f, ",threshold")?; }
+            (BinaryMode::Threshold, Some(ascertainment)) => {
+                write!(f, ",ascertain={}", ascertainment)?;
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+impl Display for Ordinal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let proportions: Vec<String> =
+            self.proportions.iter().map(|proportion| { format!("{}", proportion) }).collect();
+        write!(f, "ord({},{})", proportions.join(","), self.labels.join(","))
+    }
+}
+
+impl Display for Count {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.size {
+            None => { write!(f, "count({},{})", self.mean, self.scale) }
+            Some(size) => { write!(f, "count({},{},{})", self.mean, self.scale, size) }
+        }
+    }
+}
+
+impl Display for Survival {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "surv({},{},{}", self.rate, self.shape, self.effect)?;
+        if let Some(time) = self.admin_censoring {
+            write!(f, ",admin={}", time)?;
+        }
+        if let Some(rate) = self.random_censoring {
+            write!(f, ",rand={}", rate)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl Display for Exposure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exposure::Binary(p) => { write!(f, "bin({})", p) }
+            Exposure::Normal => { write!(f, "norm") }
+        }
+    }
+}
+
+impl Display for Effects {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Effects::Random(distribution) => { write!(f, "{}", distribution) }
+            Effects::Causal(causal) => {
+                write!(f, "causal({},{})", causal.n, causal.distribution)
+            }
+            Effects::Fixed(fixed) => { write!(f, "file({})", fixed.file) }
         }
     }
 }
@@ -151,14 +539,24 @@ impl Display for MyDistribution {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             MyDistribution::Stuck(stuck) => { write!(f, "{}", stuck.value) }
-            MyDistribution::Norm(norm) => {
-                write!(f, "norm({},{})", norm.mean(), norm.std_dev())
-            }
+            MyDistribution::Norm(norm) => { write!(f, "{}", norm) }
             MyDistribution::Pick(pick) => { write!(f, "{}", pick) }
         }
     }
 }
 
+impl Display for NormDistribution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.alpha {
+            None => { write!(f, "norm({},{})", self.normal.mean(), self.normal.std_dev()) }
+            Some(alpha) => {
+                write!(f, "norm({},{},alpha={})", self.normal.mean(), self.normal.std_dev(),
+                       alpha)
+            }
+        }
+    }
+}
+
 impl Display for PickDistribution {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let args =
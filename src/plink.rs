@@ -0,0 +1,240 @@
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use fs_err::File;
+use noodles::vcf::Record;
+use noodles::vcf::record::{AlternateBases, Chromosome, Ids, Position, ReferenceBases};
+use crate::error::{Error, ErrorKind};
+use crate::records::RecordProcessor;
+use crate::sim::genotype_sim::GenotypeSim;
+
+const BED_MAGIC: [u8; 3] = [0x6c, 0x1b, 0x01];
+const MISSING_ALLELE: &str = "0";
+
+pub(crate) fn is_plink(file: &str) -> bool {
+    file.ends_with(".bed")
+}
+
+fn sibling(bed_file: &str, extension: &str) -> String {
+    format!("{}{}", bed_file.strip_suffix(".bed").unwrap_or(bed_file), extension)
+}
+
+pub(crate) fn read_sample_ids(bed_file: &str) -> Result<Vec<String>, Error> {
+    let fam_file = sibling(bed_file, ".fam");
+    let mut sample_ids: Vec<String> = Vec::new();
+    for line in BufReader::new(File::open(&fam_file)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let sample_id = line.split_whitespace().nth(1).ok_or_else(|| {
+            Error::from(format!("Missing sample id in line '{}' of {}.", line, fam_file))
+        })?;
+        sample_ids.push(String::from(sample_id));
+    }
+    Ok(sample_ids)
+}
+
+pub(crate) fn process_plink_file<P: RecordProcessor>(bed_file: &str, record_processor: &mut P)
+                                                     -> Result<(), Error> {
+    let n_samples = read_sample_ids(bed_file)?.len();
+    let bim_file = sibling(bed_file, ".bim");
+    let mut bed_reader = BufReader::new(File::open(bed_file)?);
+    let mut magic: Vec<u8> = Vec::new();
+    (&mut bed_reader).take(BED_MAGIC.len() as u64).read_to_end(&mut magic)?;
+    if magic != BED_MAGIC {
+        return Err(Error::from(
+            format!("{} is not a variant-major PLINK 1 .bed file.", bed_file)
+        ));
+    }
+    let mut codes = vec![0u8; n_samples.div_ceil(4)];
+    for line in BufReader::new(File::open(&bim_file)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = parse_bim_line(&line).map_err(|error| {
+            let message = format!("Cannot parse line '{}' of {}", line, bim_file);
+            Error::new_wrap(ErrorKind::Phenics, message, Some(Box::new(error)))
+        })?;
+        match bed_reader.read_exact(&mut codes) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(Error::from(
+                    format!("{} has fewer variants than {}.", bed_file, bim_file)
+                ));
+            }
+            Err(error) => { return Err(Error::from(error)) }
+        }
+        let has_alt = !record.alternate_bases().is_empty();
+        let genotype_sims = decode_genotypes(&codes, n_samples, has_alt);
+        record_processor.process_genotype_sims(&record, genotype_sims)?;
+    }
+    if !bed_reader.fill_buf()?.is_empty() {
+        return Err(Error::from(format!("{} has more variants than {}.", bed_file, bim_file)));
+    }
+    Ok(())
+}
+
+fn parse_bim_line(line: &str) -> Result<Record, Error> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 6 {
+        return Err(Error::from(format!("Expected 6 fields, but got {}.", fields.len())));
+    }
+    let chromosome = chromosome_name(fields[0]).parse::<Chromosome>().map_err(|error| {
+        Error::from(format!("Invalid chromosome {}: {}", fields[0], error))
+    })?;
+    let position = fields[3].parse::<Position>()?;
+    let ids = fields[1].parse::<Ids>().map_err(|error| {
+        Error::from(format!("Invalid id {}: {}", fields[1], error))
+    })?;
+    let ref_allele = if fields[5] == MISSING_ALLELE { "N" } else { fields[5] };
+    let reference_bases = ref_allele.parse::<ReferenceBases>().map_err(|error| {
+        Error::from(format!("Invalid allele {}: {}", ref_allele, error))
+    })?;
+    let alternate_bases = match fields[4] {
+        MISSING_ALLELE => { AlternateBases::default() }
+        alt_allele => {
+            alt_allele.parse::<AlternateBases>().map_err(|error| {
+                Error::from(format!("Invalid allele {}: {}", alt_allele, error))
+            })?
+        }
+    };
+    let record =
+        Record::builder().set_chromosome(chromosome).set_position(position).set_ids(ids)
+            .set_reference_bases(reference_bases).set_alternate_bases(alternate_bases)
+            .build().map_err(|error| { Error::from(error.to_string()) })?;
+    Ok(record)
+}
+
+fn chromosome_name(code: &str) -> &str {
+    match code {
+        "23" => { "X" }
+        "24" => { "Y" }
+        "25" => { "XY" }
+        "26" => { "MT" }
+        _ => { code }
+    }
+}
+
+fn decode_genotypes(codes: &[u8], n_samples: usize, has_alt: bool)
+                    -> Vec<Option<GenotypeSim>> {
+    (0..n_samples).map(|i_sample| {
+        let code = (codes[i_sample / 4] >> (2 * (i_sample % 4))) & 0b11;
+        Some(genotype_sim(code, has_alt))
+    }).collect()
+}
+
+fn genotype_sim(code: u8, has_alt: bool) -> GenotypeSim {
+    let (alt_count, n_known_alleles) = match code {
+        0b00 => { (2.0, 2) }
+        0b10 => { (1.0, 2) }
+        0b11 => { (0.0, 2) }
+        _ => { (0.0, 0) }
+    };
+    let (dosages, het_probs) = if has_alt {
+        (vec![alt_count], vec![if alt_count == 1.0 { 1.0 } else { 0.0 }])
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    GenotypeSim { dosages, het_probs, n_unknown_alleles: 2 - n_known_alleles, n_known_alleles,
+        is_haploid: false }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    fn summarize(genotype_sims: &[Option<GenotypeSim>]) -> Vec<(Vec<f64>, Vec<f64>, u64)> {
+        genotype_sims.iter().map(|genotype_sim| {
+            let genotype_sim = genotype_sim.as_ref().unwrap();
+            (genotype_sim.dosages.clone(), genotype_sim.het_probs.clone(),
+             genotype_sim.n_known_alleles)
+        }).collect()
+    }
+
+    #[test]
+    fn decode_codes_of_one_byte() {
+        let codes = [0b11_10_01_00u8];
+        let genotype_sims = decode_genotypes(&codes, 4, true);
+        assert_eq!(summarize(&genotype_sims), vec![
+            (vec![2.0], vec![0.0], 2),
+            (vec![0.0], vec![0.0], 0),
+            (vec![1.0], vec![1.0], 2),
+            (vec![0.0], vec![0.0], 2),
+        ]);
+        let missing = genotype_sims[1].as_ref().unwrap();
+        assert_eq!(missing.n_unknown_alleles, 2);
+    }
+
+    #[test]
+    fn decode_ignores_padding_of_last_byte() {
+        for padding in [0b00u8, 0b01, 0b10, 0b11] {
+            let last = 0b10 | (padding << 2) | (padding << 4) | (padding << 6);
+            let codes = [0b00_00_00_11u8, 0b10_11_01_00, last];
+            let genotype_sims = decode_genotypes(&codes, 9, true);
+            let dosages: Vec<f64> =
+                summarize(&genotype_sims).into_iter().map(|(dosages, _, _)| { dosages[0] })
+                    .collect();
+            assert_eq!(dosages, vec![0.0, 2.0, 2.0, 2.0, 2.0, 0.0, 0.0, 1.0, 1.0]);
+            let n_known: Vec<u64> =
+                summarize(&genotype_sims).into_iter().map(|(_, _, n_known)| { n_known })
+                    .collect();
+            assert_eq!(n_known, vec![2, 2, 2, 2, 2, 0, 2, 2, 2]);
+        }
+    }
+
+    #[test]
+    fn decode_without_alternate_allele() {
+        let genotype_sims = decode_genotypes(&[0b11_10_01_00u8], 4, false);
+        assert!(summarize(&genotype_sims).iter().all(|(dosages, het_probs, _)| {
+            dosages.is_empty() && het_probs.is_empty()
+        }));
+    }
+
+    struct DosageCollector {
+        records: Vec<(String, usize, Vec<f64>)>,
+    }
+
+    impl RecordProcessor for DosageCollector {
+        fn process_record(&mut self, _record: &Record) -> Result<(), Error> {
+            panic!("Expected genotypes to be decoded from .bed file.")
+        }
+        fn process_genotype_sims(&mut self, record: &Record,
+                                 genotype_sims: Vec<Option<GenotypeSim>>)
+                                 -> Result<(), Error> {
+            let dosages: Vec<f64> = genotype_sims.iter().map(|genotype_sim| {
+                let genotype_sim = genotype_sim.as_ref().unwrap();
+                if genotype_sim.n_known_alleles == 0 { -1.0 } else { genotype_sim.dosages[0] }
+            }).collect();
+            self.records.push((record.chromosome().to_string(), usize::from(record.position()),
+                               dosages));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_bed_bim_and_fam() {
+        let prefix = temp_path("plink");
+        let fam = "f1 s1 0 0 1 -9\nf2 s2 0 0 2 -9\nf3 s3 0 0 0 -9\n\
+        f4 s4 0 0 1 -9\nf5 s5 0 0 2 -9\n";
+        let bim = "1\trs1\t0\t100\tG\tA\n23\trs2\t0\t200\tT\tC\n";
+        let bed = [0x6c, 0x1b, 0x01, 0b11_10_01_00, 0b00, 0b00_01_10_11, 0b11_11_11_10];
+        std::fs::write(format!("{}.fam", prefix), fam).unwrap();
+        std::fs::write(format!("{}.bim", prefix), bim).unwrap();
+        std::fs::write(format!("{}.bed", prefix), bed).unwrap();
+        let bed_file = format!("{}.bed", prefix);
+        assert_eq!(read_sample_ids(&bed_file).unwrap(), vec!["s1", "s2", "s3", "s4", "s5"]);
+        let mut collector = DosageCollector { records: Vec::new() };
+        process_plink_file(&bed_file, &mut collector).unwrap();
+        assert_eq!(collector.records, vec![
+            (String::from("1"), 100, vec![2.0, -1.0, 1.0, 0.0, 2.0]),
+            (String::from("X"), 200, vec![0.0, 1.0, -1.0, 2.0, 1.0]),
+        ]);
+        std::fs::write(&bed_file, &bed[..6]).unwrap();
+        assert!(process_plink_file(&bed_file, &mut collector).is_err());
+        for extension in ["fam", "bim", "bed"] {
+            std::fs::remove_file(format!("{}.{}", prefix, extension)).unwrap();
+        }
+    }
+}
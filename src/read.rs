@@ -1,31 +1,235 @@
 use crate::sim::Sim;
-use crate::error::Error;
+use crate::error::{Error, ErrorKind};
 use noodles::{bgzf, vcf};
 use fs_err::File;
-use noodles::vcf::Header;
-use crate::phenotype::Phenotype;
-use std::io::{stdin, BufRead, BufReader};
+use noodles::vcf::{Header, Record};
+use std::io::{stdin, BufRead, BufReader, Read, Seek};
+use std::sync::Mutex;
 use crate::records::{SimProcessor, RecordProcessor};
+use crate::effects::EffectsWriter;
+use crate::sim::allele_sim::AlleleSimGen;
+use crate::compensation::Compensation;
+use crate::sim::genotype_sim::DosageField;
+use crate::chunks::Chunk;
+use crate::record_reader::RecordReader;
+use crate::plink;
 
-pub(crate) fn read_vcf_file(file: &str, phenotypes: &[Phenotype]) -> Result<Sim, Error> {
-    let reader = bgzf::Reader::new(File::open(file)?);
-    read_vcf_reader(reader, phenotypes)
+pub(crate) fn read_sample_ids(file: &str) -> Result<Vec<String>, Error> {
+    if plink::is_plink(file) {
+        plink::read_sample_ids(file)
+    } else {
+        let (_, variant_header) = RecordReader::open(File::open(file)?)?;
+        Ok(variant_header.sample_ids())
+    }
+}
+
+pub(crate) fn read_vcf_chunks(chunks: &[&Chunk], sim: &mut Sim, allele_sim_gen: &AlleleSimGen,
+                              effects_writer: Option<&Mutex<EffectsWriter>>,
+                              compensation: Option<&Compensation>, dosage_field: DosageField)
+                              -> Result<(), Error> {
+    let sample_ids = sim.sample_ids();
+    let mut sim_processor =
+        SimProcessor::new(sim, allele_sim_gen, effects_writer, compensation, dosage_field)?;
+    for chunk in chunks {
+        println!("Next reading {}", chunk);
+        process_vcf_chunk(chunk, &sample_ids, &mut sim_processor).map_err(|error| {
+            let message = format!("Failed reading {}", chunk);
+            Error::new_wrap(ErrorKind::Phenics, message, Some(Box::new(error)))
+        })?;
+    }
+    Ok(())
 }
 
-pub(crate) fn read_vcf_stdin(phenotypes: &[Phenotype]) -> Result<Sim, Error> {
+pub(crate) fn read_vcf_stdin(allele_sim_gen: &AlleleSimGen,
+                             effects_writer: Option<&Mutex<EffectsWriter>>,
+                             compensation: Option<&Compensation>, dosage_field: DosageField)
+                             -> Result<Sim, Error> {
     let reader = BufReader::new(stdin());
-    read_vcf_reader(reader, phenotypes)
+    read_vcf_reader(reader, allele_sim_gen, effects_writer, compensation, dosage_field)
 }
 
-fn read_vcf_reader<R: BufRead>(reader: R, phenotypes: &[Phenotype]) -> Result<Sim, Error> {
+pub(crate) fn process_vcf_file<P: RecordProcessor>(file: &str, record_processor: &mut P)
+                                                   -> Result<(), Error> {
+    if plink::is_plink(file) {
+        return plink::process_plink_file(file, record_processor);
+    }
+    let (mut record_reader, variant_header) = RecordReader::open(File::open(file)?)?;
+    while let Some(record) = record_reader.read_record(&variant_header)? {
+        record_processor.process_record(&record)?;
+    }
+    Ok(())
+}
+
+fn process_vcf_chunk<P: RecordProcessor>(chunk: &Chunk, sample_ids: &[String],
+                                         record_processor: &mut P) -> Result<(), Error> {
+    if plink::is_plink(&chunk.file) {
+        check_sample_ids(&chunk.file, &plink::read_sample_ids(&chunk.file)?, sample_ids)?;
+        return plink::process_plink_file(&chunk.file, record_processor);
+    }
+    let (mut record_reader, variant_header) = RecordReader::open(File::open(&chunk.file)?)?;
+    check_sample_ids(&chunk.file, &variant_header.sample_ids(), sample_ids)?;
+    if chunk.n_chunks > 1 {
+        if let RecordReader::Vcf(vcf_reader) = &mut record_reader {
+            return process_vcf_lines(vcf_reader.get_mut(), chunk, &variant_header.header,
+                                     record_processor);
+        }
+    }
+    while let Some(record) = record_reader.read_record(&variant_header)? {
+        record_processor.process_record(&record)?;
+    }
+    Ok(())
+}
+
+fn check_sample_ids(file: &str, file_sample_ids: &[String], sample_ids: &[String])
+                    -> Result<(), Error> {
+    if file_sample_ids != sample_ids {
+        Err(Error::from(
+            format!("Samples in {} need to be the same as in the first input.", file)
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn process_vcf_lines<R: Read + Seek, P: RecordProcessor>(reader: &mut bgzf::Reader<R>,
+                                                         chunk: &Chunk, header: &Header,
+                                                         record_processor: &mut P)
+                                                         -> Result<(), Error> {
+    reader.seek(chunk.seek_to)?;
+    let mut line = String::new();
+    loop {
+        if reader.fill_buf()?.is_empty() {
+            break;
+        }
+        let line_start = reader.virtual_position();
+        if chunk.end.map(|end| { line_start >= end }).unwrap_or(false) {
+            break;
+        }
+        line.clear();
+        reader.read_line(&mut line)?;
+        if line_start < chunk.start || line.starts_with('#') || line.trim_end().is_empty() {
+            continue;
+        }
+        let record = Record::try_from_str(line.trim_end_matches(['\n', '\r']), header)?;
+        record_processor.process_record(&record)?;
+    }
+    Ok(())
+}
+
+fn read_vcf_reader<R: BufRead>(reader: R, allele_sim_gen: &AlleleSimGen,
+                               effects_writer: Option<&Mutex<EffectsWriter>>,
+                               compensation: Option<&Compensation>, dosage_field: DosageField)
+                               -> Result<Sim, Error> {
     let mut vcf_reader = vcf::Reader::new(reader);
     let header = vcf_reader.read_header()?.parse::<Header>()?;
     let sample_ids: Vec<String> = header.sample_names().iter().map(String::from).collect();
-    let mut sim = Sim::new(sample_ids, phenotypes);
-    let mut sim_processor = SimProcessor::new(&mut sim, phenotypes);
+    if let Some(compensation) = compensation {
+        compensation.report_uncompensated(&sample_ids)?;
+    }
+    let mut sim = Sim::new(sample_ids, allele_sim_gen.phenotypes, allele_sim_gen.seed,
+                           allele_sim_gen.picked());
+    let mut sim_processor =
+        SimProcessor::new(&mut sim, allele_sim_gen, effects_writer, compensation, dosage_field)?;
     for record in vcf_reader.records(&header) {
         let record = record?;
         sim_processor.process_record(&record)?;
     }
     Ok(sim)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunks;
+    use crate::chunks::tests::write_bgzf;
+
+    const HEADER: &str = "##fileformat=VCFv4.2\n\
+    #CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\ts2\n";
+
+    struct PositionCollector {
+        positions: Vec<usize>,
+    }
+
+    impl RecordProcessor for PositionCollector {
+        fn process_record(&mut self, record: &Record) -> Result<(), Error> {
+            self.positions.push(usize::from(record.position()));
+            Ok(())
+        }
+    }
+
+    fn record_line(position: usize) -> String {
+        format!("chr1\t{}\t.\tA\tG\t.\t.\t.\tGT\t0/1\t1/1\n", position)
+    }
+
+    fn read_all_chunks(file: &str, n_chunks: usize) -> Vec<Vec<usize>> {
+        let sample_ids = vec![String::from("s1"), String::from("s2")];
+        chunks::split(file, n_chunks).unwrap().iter().map(|chunk| {
+            let mut collector = PositionCollector { positions: Vec::new() };
+            process_vcf_chunk(chunk, &sample_ids, &mut collector).unwrap();
+            collector.positions
+        }).collect()
+    }
+
+    fn check_each_record_read_once(file: &str, positions: &[usize], n_chunks: usize) {
+        let positions_by_chunk = read_all_chunks(file, n_chunks);
+        let positions_read: Vec<usize> = positions_by_chunk.concat();
+        assert_eq!(positions_read, positions, "with {} chunks", n_chunks);
+    }
+
+    #[test]
+    fn line_spanning_block_boundary_is_read_once() {
+        let positions: Vec<usize> = (1..=9).map(|i| { i * 100 }).collect();
+        let text: String =
+            positions.iter().map(|position| { record_line(*position) }).collect();
+        let mut blocks: Vec<String> = vec![String::from(HEADER)];
+        let mut rest = text.as_str();
+        while !rest.is_empty() {
+            let (block, tail) = rest.split_at(rest.len().min(37));
+            blocks.push(String::from(block));
+            rest = tail;
+        }
+        let file = write_bgzf("spanning.vcf.gz", &blocks);
+        for n_chunks in 1..=blocks.len() {
+            check_each_record_read_once(&file, &positions, n_chunks);
+        }
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn header_and_records_in_first_and_last_chunk() {
+        let positions: Vec<usize> = vec![10, 20, 30, 40];
+        let blocks: Vec<String> = vec![
+            format!("{}{}", HEADER, record_line(10)),
+            format!("{}{}", record_line(20), record_line(30)),
+            record_line(40),
+        ];
+        let file = write_bgzf("first-last.vcf.gz", &blocks);
+        let positions_by_chunk = read_all_chunks(&file, 3);
+        assert_eq!(positions_by_chunk, vec![vec![10], vec![20, 30], vec![40]]);
+        check_each_record_read_once(&file, &positions, 2);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn single_block_file() {
+        let positions: Vec<usize> = vec![5, 6, 7];
+        let text: String =
+            positions.iter().map(|position| { record_line(*position) }).collect();
+        let file = write_bgzf("single-block.vcf.gz", &[format!("{}{}", HEADER, text)]);
+        for n_chunks in 1..=4 {
+            check_each_record_read_once(&file, &positions, n_chunks);
+        }
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn more_threads_than_blocks() {
+        let positions: Vec<usize> = vec![1, 2, 3];
+        let blocks: Vec<String> = vec![
+            String::from(HEADER), record_line(1), format!("{}{}", record_line(2), record_line(3)),
+        ];
+        let file = write_bgzf("many-threads.vcf.gz", &blocks);
+        check_each_record_read_once(&file, &positions, 50);
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+use std::any::Any;
+use std::io::{BufRead, Read};
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use noodles::{bcf, bgzf, vcf};
+use noodles::bcf::header::StringMaps;
+use noodles::vcf::{Header, Record};
+use crate::error::Error;
+
+const BCF_MAGIC: &[u8] = b"BCF";
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Format {
+    Vcf,
+    Bcf,
+}
+
+pub(crate) struct VariantHeader {
+    pub(crate) header: Header,
+    string_maps: Option<StringMaps>,
+}
+
+pub(crate) enum RecordReader<R> {
+    Vcf(vcf::Reader<bgzf::Reader<R>>),
+    Bcf(bcf::Reader<bgzf::Reader<R>>, Box<bcf::Record>),
+}
+
+pub(crate) fn detect_format<R: Read>(bgzf_reader: &mut bgzf::Reader<R>) -> Result<Format, Error> {
+    if bgzf_reader.fill_buf()?.starts_with(BCF_MAGIC) {
+        Ok(Format::Bcf)
+    } else {
+        Ok(Format::Vcf)
+    }
+}
+
+impl VariantHeader {
+    pub(crate) fn format(&self) -> Format {
+        match self.string_maps {
+            None => { Format::Vcf }
+            Some(_) => { Format::Bcf }
+        }
+    }
+    pub(crate) fn sample_ids(&self) -> Vec<String> {
+        self.header.sample_names().iter().map(String::from).collect()
+    }
+}
+
+impl<R: Read> RecordReader<R> {
+    pub(crate) fn open(inner: R) -> Result<(RecordReader<R>, VariantHeader), Error> {
+        let mut bgzf_reader = bgzf::Reader::new(inner);
+        match detect_format(&mut bgzf_reader)? {
+            Format::Vcf => {
+                let mut vcf_reader = vcf::Reader::new(bgzf_reader);
+                let header = vcf_reader.read_header()?.parse::<Header>()?;
+                let variant_header = VariantHeader { header, string_maps: None };
+                Ok((RecordReader::Vcf(vcf_reader), variant_header))
+            }
+            Format::Bcf => {
+                let mut bcf_reader = bcf::Reader::from(bgzf_reader);
+                bcf_reader.read_file_format()?;
+                let raw_header = bcf_reader.read_header()?;
+                let header = raw_header.parse::<Header>()?;
+                let string_maps = raw_header.parse::<StringMaps>().map_err(|error| {
+                    Error::from(format!("Cannot parse BCF header: {}", error))
+                })?;
+                let variant_header = VariantHeader { header, string_maps: Some(string_maps) };
+                Ok((RecordReader::Bcf(bcf_reader, Box::default()), variant_header))
+            }
+        }
+    }
+    pub(crate) fn new(bgzf_reader: bgzf::Reader<R>, variant_header: &VariantHeader)
+                      -> RecordReader<R> {
+        match variant_header.format() {
+            Format::Vcf => { RecordReader::Vcf(vcf::Reader::new(bgzf_reader)) }
+            Format::Bcf => {
+                RecordReader::Bcf(bcf::Reader::from(bgzf_reader), Box::default())
+            }
+        }
+    }
+    pub(crate) fn read_record(&mut self, variant_header: &VariantHeader)
+                              -> Result<Option<Record>, Error> {
+        match self {
+            RecordReader::Vcf(vcf_reader) => {
+                let mut line = String::new();
+                if vcf_reader.read_record(&mut line)? == 0 {
+                    Ok(None)
+                } else {
+                    Ok(Some(Record::try_from_str(&line, &variant_header.header)?))
+                }
+            }
+            RecordReader::Bcf(bcf_reader, bcf_record) => {
+                let string_maps = variant_header.string_maps.as_ref().ok_or_else(|| {
+                    Error::from("Need BCF header to read BCF records.")
+                })?;
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    if bcf_reader.read_record(bcf_record)? == 0 {
+                        Ok(None)
+                    } else {
+                        let record =
+                            bcf_record.try_into_vcf_record(&variant_header.header, string_maps)?;
+                        Ok(Some(record))
+                    }
+                }));
+                match result {
+                    Ok(result) => { result }
+                    Err(payload) => {
+                        Err(Error::from(
+                            format!("Cannot decode BCF record: {}",
+                                    panic_message(payload.as_ref()))
+                        ))
+                    }
+                }
+            }
+        }
+    }
+    pub(crate) fn virtual_position(&self) -> bgzf::VirtualPosition {
+        match self {
+            RecordReader::Vcf(vcf_reader) => { vcf_reader.get_ref().virtual_position() }
+            RecordReader::Bcf(bcf_reader, _) => { bcf_reader.virtual_position() }
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        String::from(*message)
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
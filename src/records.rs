@@ -1,63 +1,321 @@
 use noodles::vcf::Record;
 use crate::error::Error;
-use crate::sim::genotype_sim::GenotypeSim;
-use crate::sim::allele_sim::AlleleSim;
-use crate::locus::Locus;
+use crate::sim::genotype_sim::{allele_frequencies, DosageField, GenotypeSim};
+use crate::sim::allele_sim::{AlleleSim, AlleleSimGen, causal_key, epi_key};
+use crate::locus::{Allele, Locus};
 use crate::sim::Sim;
 use crate::phenotype::Phenotype;
+use crate::phenotype::pheno_sim::Effects;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::Mutex;
+use crate::effects::EffectsWriter;
+use crate::compensation::{Compensation, Sex};
 
 pub(crate) trait RecordProcessor {
     fn process_record(&mut self, record: &Record) -> Result<(), Error>;
-    fn has_processed(&self) -> bool;
-    fn reset_has_processed(&mut self);
+    fn process_genotype_sims(&mut self, record: &Record,
+                             _genotype_sims: Vec<Option<GenotypeSim>>) -> Result<(), Error> {
+        self.process_record(record)
+    }
 }
 
 pub(crate) struct SimProcessor<'a> {
-    phenotypes: &'a [Phenotype],
-    has_processed: bool,
     sim: &'a mut Sim,
+    allele_sim_gen: &'a AlleleSimGen<'a>,
+    effects_writer: Option<&'a Mutex<EffectsWriter>>,
+    compensation: Option<(&'a Compensation, Vec<Sex>)>,
+    dosage_field: DosageField,
+    epi_columns: Vec<Option<usize>>,
+}
+
+pub(crate) struct RecordPrinter {}
+
+pub(crate) struct CausalPicker<'a> {
+    phenotypes: &'a [Phenotype],
+    seed: u64,
+    keys: Vec<Option<SmallestKeys>>,
+    epi_keys: Vec<Option<SmallestKeys>>,
+}
+
+#[derive(Default)]
+struct SmallestKeys {
+    heap: BinaryHeap<u64>,
+    members: HashSet<u64>,
+}
+
+pub(crate) struct Picks {
+    pub(crate) causal_thresholds: Vec<Option<u64>>,
+    pub(crate) epi_pairs: Vec<Option<HashMap<u64, usize>>>,
 }
 
-pub(crate) struct RecordPrinter {
-    has_processed: bool,
+pub(crate) struct SexInferrer<'a> {
+    compensation: &'a Compensation,
+    dosage_field: DosageField,
+    observed_hets: Vec<f64>,
+    expected_hets: Vec<f64>,
 }
 
 impl SimProcessor<'_> {
-    pub(crate) fn new<'a>(sim: &'a mut Sim, phenotypes: &'a [Phenotype]) -> SimProcessor<'a> {
-        let has_processed = false;
-        SimProcessor { sim, phenotypes, has_processed }
+    pub(crate) fn new<'a>(sim: &'a mut Sim, allele_sim_gen: &'a AlleleSimGen<'a>,
+                          effects_writer: Option<&'a Mutex<EffectsWriter>>,
+                          compensation: Option<&'a Compensation>,
+                          dosage_field: DosageField) -> Result<SimProcessor<'a>, Error> {
+        let compensation = match compensation {
+            None => { None }
+            Some(compensation) => {
+                let sexes = compensation.sexes_for(&sim.sample_ids())?;
+                sim.set_sexes(&sexes);
+                Some((compensation, sexes))
+            }
+        };
+        let epi_columns: Vec<Option<usize>> =
+            allele_sim_gen.phenotypes.iter().map(|phenotype| {
+                sim.epi_column(&phenotype.name)
+            }).collect();
+        Ok(SimProcessor {
+            sim, allele_sim_gen, effects_writer, compensation, dosage_field, epi_columns,
+        })
     }
 }
 
 impl RecordPrinter {
     pub(crate) fn new() -> RecordPrinter {
-        let has_processed = false;
-        RecordPrinter { has_processed }
+        RecordPrinter {}
+    }
+}
+
+impl CausalPicker<'_> {
+    pub(crate) fn new<'a>(phenotypes: &'a [Phenotype], seed: u64) -> CausalPicker<'a> {
+        let keys =
+            phenotypes.iter().map(|phenotype| {
+                match &phenotype.sim.effects {
+                    Effects::Causal(_) => { Some(SmallestKeys::default()) }
+                    Effects::Random(_) | Effects::Fixed(_) => { None }
+                }
+            }).collect();
+        let epi_keys =
+            phenotypes.iter().map(|phenotype| {
+                phenotype.sim.epistasis.as_ref().map(|_| { SmallestKeys::default() })
+            }).collect();
+        CausalPicker { phenotypes, seed, keys, epi_keys }
+    }
+    pub(crate) fn is_needed(&self) -> bool {
+        self.keys.iter().chain(self.epi_keys.iter()).any(|keys| { keys.is_some() })
+    }
+    pub(crate) fn what_is_picked(&self) -> &'static str {
+        let picks_causal = self.keys.iter().any(|keys| { keys.is_some() });
+        let picks_epi = self.epi_keys.iter().any(|keys| { keys.is_some() });
+        match (picks_causal, picks_epi) {
+            (true, true) => { "causal variants and epistatic pairs" }
+            (false, true) => { "epistatic pairs" }
+            _ => { "causal variants" }
+        }
+    }
+    pub(crate) fn into_picks(self) -> Picks {
+        let CausalPicker { phenotypes, keys, epi_keys, .. } = self;
+        let causal_thresholds =
+            keys.into_iter().zip(phenotypes.iter()).map(|(keys, phenotype)| {
+                match (keys, &phenotype.sim.effects) {
+                    (Some(keys), Effects::Causal(causal)) => {
+                        if keys.len() < causal.n {
+                            println!("Warning: asked for {} causal variants for {}, but only \
+                            have {}.", causal.n, phenotype.name, keys.len());
+                            Some(u64::MAX)
+                        } else {
+                            keys.max()
+                        }
+                    }
+                    _ => { None }
+                }
+            }).collect();
+        let epi_pairs =
+            epi_keys.into_iter().zip(phenotypes.iter()).map(|(keys, phenotype)| {
+                match (keys, &phenotype.sim.epistasis) {
+                    (Some(keys), Some(epistasis)) => {
+                        let keys = keys.into_sorted_vec();
+                        let n_pairs = keys.len() / 2;
+                        if n_pairs < epistasis.n_pairs {
+                            println!("Warning: asked for {} epistatic pairs for {}, but only \
+                            have {}.", epistasis.n_pairs, phenotype.name, n_pairs);
+                        }
+                        let pairs: HashMap<u64, usize> =
+                            keys.into_iter().take(2 * n_pairs).enumerate()
+                                .map(|(i, key)| { (key, i / 2) }).collect();
+                        Some(pairs)
+                    }
+                    _ => { None }
+                }
+            }).collect();
+        Picks { causal_thresholds, epi_pairs }
+    }
+}
+
+impl SmallestKeys {
+    fn keep_smallest(&mut self, key: u64, n: usize) {
+        if self.members.contains(&key) {
+            return;
+        }
+        if self.heap.len() < n {
+            self.heap.push(key);
+            self.members.insert(key);
+        } else if self.max().map(|key_max| { key < key_max }).unwrap_or(false) {
+            if let Some(key_max) = self.heap.pop() {
+                self.members.remove(&key_max);
+            }
+            self.heap.push(key);
+            self.members.insert(key);
+        }
+    }
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+    fn max(&self) -> Option<u64> {
+        self.heap.peek().copied()
+    }
+    fn into_sorted_vec(self) -> Vec<u64> {
+        self.heap.into_sorted_vec()
+    }
+}
+
+impl SexInferrer<'_> {
+    pub(crate) fn new<'a>(compensation: &'a Compensation, dosage_field: DosageField)
+                          -> SexInferrer<'a> {
+        let observed_hets: Vec<f64> = Vec::new();
+        let expected_hets: Vec<f64> = Vec::new();
+        SexInferrer { compensation, dosage_field, observed_hets, expected_hets }
+    }
+    pub(crate) fn into_sexes(self) -> Vec<Sex> {
+        let sexes: Vec<Sex> =
+            self.observed_hets.iter().zip(self.expected_hets.iter())
+                .map(|(observed, expected)| {
+                    if *expected > 0.0 {
+                        let inbreeding = 1.0 - observed / expected;
+                        if inbreeding > 0.8 {
+                            Sex::Male
+                        } else if inbreeding < 0.2 {
+                            Sex::Female
+                        } else {
+                            Sex::Unknown
+                        }
+                    } else {
+                        Sex::Unknown
+                    }
+                }).collect();
+        let n_males = sexes.iter().filter(|sex| { **sex == Sex::Male }).count();
+        let n_females = sexes.iter().filter(|sex| { **sex == Sex::Female }).count();
+        println!("Inferred {} males, {} females and {} of unknown sex.", n_males, n_females,
+                 sexes.len() - n_males - n_females);
+        sexes
     }
 }
 
 impl RecordProcessor for SimProcessor<'_> {
     fn process_record(&mut self, record: &Record) -> Result<(), Error> {
-        self.has_processed = true;
-        let genotypes = record.genotypes().genotypes()?;
-        let allele_sims =
-            record.alternate_bases().iter().map(|_alts| {
-                AlleleSim::from_phenotypes(self.phenotypes)
-            })
-                .collect::<Vec<AlleleSim>>();
+        let genotype_sims = self.dosage_field.genotype_sims(record)?;
+        self.process_genotype_sims(record, genotype_sims)
+    }
+    fn process_genotype_sims(&mut self, record: &Record,
+                             mut genotype_sims: Vec<Option<GenotypeSim>>) -> Result<(), Error> {
         let locus = Locus::new(record.chromosome(), &record.position());
-        self.sim.check_same_size_as_samples(&genotypes, &locus, "genotypes")?;
-        for (i_sample, genotype) in genotypes.iter().enumerate() {
-            let genotype_sim =
-                genotype.as_ref()
-                    .map(|genotype| { GenotypeSim::new(genotype, allele_sims.len()) });
-            self.sim.add_genotype_sim(&genotype_sim, i_sample, &allele_sims);
+        self.sim.check_same_size_as_samples(&genotype_sims, &locus, "genotypes")?;
+        let n_alt = record.alternate_bases().len();
+        let freqs = allele_frequencies(&genotype_sims, n_alt);
+        if let Some((compensation, sexes)) = &self.compensation {
+            compensation.apply(&locus, sexes, &mut genotype_sims);
+        }
+        let ref_bases = record.reference_bases().to_string();
+        let mut alleles: Vec<Allele> = Vec::new();
+        let mut allele_sims: Vec<AlleleSim> = Vec::new();
+        for (i_alt, alt) in record.alternate_bases().iter().enumerate() {
+            let allele = Allele::new(locus.clone(), ref_bases.clone(), alt.to_string());
+            let allele_sim = self.allele_sim_gen.new_allele_sim(&allele, freqs[i_alt]);
+            if let Some(effects_writer) = self.effects_writer {
+                effects_writer.lock().unwrap()
+                    .write(&allele, &record.ids().to_string(), &allele_sim)?;
+            }
+            alleles.push(allele);
+            allele_sims.push(allele_sim);
+        }
+        for (i_sample, genotype_sim) in genotype_sims.iter().enumerate() {
+            self.sim.add_genotype_sim(genotype_sim, i_sample, &allele_sims);
+        }
+        for (i_alt, allele_sim) in allele_sims.iter().enumerate() {
+            for (i_pheno, i_pair) in &allele_sim.epi_slots {
+                let dosages: Vec<f64> = genotype_sims.iter().map(|genotype_sim| {
+                    genotype_sim.as_ref().map(|genotype_sim| { genotype_sim.dosages[i_alt] })
+                        .unwrap_or(0.0)
+                }).collect();
+                let allele = &alleles[i_alt];
+                let paired = self.allele_sim_gen.pair_up(*i_pheno, *i_pair, allele, &dosages);
+                if let Some((allele_first, dosages_first, effect)) = paired {
+                    if let Some(i_column) = self.epi_columns[*i_pheno] {
+                        self.sim.add_epistasis(i_column, &dosages_first, &dosages, effect);
+                    }
+                    if let Some(effects_writer) = self.effects_writer {
+                        let name = &self.allele_sim_gen.phenotypes[*i_pheno].name;
+                        effects_writer.lock().unwrap()
+                            .write_pair(name, *i_pair, &allele_first, allele, effect)?;
+                    }
+                }
+            }
         }
         self.sim.count_record();
         Ok(())
     }
-    fn has_processed(&self) -> bool { self.has_processed }
-    fn reset_has_processed(&mut self) { self.has_processed = false; }
+}
+
+impl RecordProcessor for CausalPicker<'_> {
+    fn process_record(&mut self, record: &Record) -> Result<(), Error> {
+        let locus = Locus::new(record.chromosome(), &record.position());
+        let ref_bases = record.reference_bases().to_string();
+        for alt in record.alternate_bases().iter() {
+            let allele = Allele::new(locus.clone(), ref_bases.clone(), alt.to_string());
+            let allele_seed = allele.derive_seed(self.seed);
+            for (phenotype, keys) in self.phenotypes.iter().zip(self.keys.iter_mut()) {
+                if let (Some(keys), Effects::Causal(causal)) = (keys, &phenotype.sim.effects) {
+                    keys.keep_smallest(causal_key(allele_seed, phenotype), causal.n);
+                }
+            }
+            for (phenotype, keys) in self.phenotypes.iter().zip(self.epi_keys.iter_mut()) {
+                if let (Some(keys), Some(epistasis)) = (keys, &phenotype.sim.epistasis) {
+                    keys.keep_smallest(epi_key(allele_seed, phenotype), 2 * epistasis.n_pairs);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RecordProcessor for SexInferrer<'_> {
+    fn process_record(&mut self, record: &Record) -> Result<(), Error> {
+        let genotype_sims = self.dosage_field.genotype_sims(record)?;
+        self.process_genotype_sims(record, genotype_sims)
+    }
+    fn process_genotype_sims(&mut self, record: &Record,
+                             genotype_sims: Vec<Option<GenotypeSim>>) -> Result<(), Error> {
+        let locus = Locus::new(record.chromosome(), &record.position());
+        if !self.compensation.covers(&locus.chrom) || self.compensation.is_escaped(&locus) {
+            return Ok(());
+        }
+        let n_alt = record.alternate_bases().len();
+        let freqs = allele_frequencies(&genotype_sims, n_alt);
+        let ref_freq = 1.0 - freqs.iter().sum::<f64>();
+        let expected_het =
+            1.0 - ref_freq * ref_freq - freqs.iter().map(|freq| { freq * freq }).sum::<f64>();
+        if self.expected_hets.is_empty() {
+            self.observed_hets = vec![0.0; genotype_sims.len()];
+            self.expected_hets = vec![0.0; genotype_sims.len()];
+        }
+        for (i_sample, genotype_sim) in genotype_sims.iter().enumerate() {
+            if let Some(genotype_sim) = genotype_sim {
+                self.expected_hets[i_sample] += expected_het;
+                if genotype_sim.is_heterozygous() {
+                    self.observed_hets[i_sample] += 1.0;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl RecordProcessor for RecordPrinter {
@@ -65,6 +323,86 @@ impl RecordProcessor for RecordPrinter {
         println!("{}", record);
         Ok(())
     }
-    fn has_processed(&self) -> bool { self.has_processed }
-    fn reset_has_processed(&mut self) { self.has_processed = false; }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use noodles::vcf::Record;
+    use noodles::vcf::record::{AlternateBases, Chromosome, Position, ReferenceBases};
+    use crate::compensation::{Compensation, Sex};
+    use crate::sim::genotype_sim::{DosageField, GenotypeSim};
+    use crate::phenotype::{parse, Definition, Phenotype};
+    use super::{CausalPicker, RecordProcessor, SexInferrer};
+    use crate::test_util::temp_path;
+
+    fn record(chrom: &str, pos: usize) -> Record {
+        Record::builder().set_chromosome(chrom.parse::<Chromosome>().unwrap())
+            .set_position(Position::from(pos))
+            .set_reference_bases("A".parse::<ReferenceBases>().unwrap())
+            .set_alternate_bases("G".parse::<AlternateBases>().unwrap())
+            .build().unwrap()
+    }
+
+    fn parse_phenotypes(string: &str) -> Vec<Phenotype> {
+        match parse::parse(string).unwrap() {
+            Definition::Phenotypes(phenotypes) => { phenotypes }
+            _ => { panic!("Expected phenotypes.") }
+        }
+    }
+
+    fn picked_pairs(n_pairs: usize, keys: &[u64]) -> Vec<(u64, usize)> {
+        let phenotypes =
+            parse_phenotypes(&format!("foo=norm(0,1),0.3,epi=pairs({},0.1)", n_pairs));
+        let mut causal_picker = CausalPicker::new(&phenotypes, 42);
+        for key in keys {
+            causal_picker.epi_keys[0].as_mut().unwrap().keep_smallest(*key, 2 * n_pairs);
+        }
+        let mut pairs: Vec<(u64, usize)> =
+            causal_picker.into_picks().epi_pairs[0].as_ref().unwrap().iter()
+                .map(|(key, i_pair)| { (*key, *i_pair) }).collect();
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn epistatic_pairs_are_formed_in_key_order() {
+        let pairs = picked_pairs(2, &[50, 10, 70, 40, 20, 30, 20]);
+        assert_eq!(pairs, vec![(10, 0), (20, 0), (30, 1), (40, 1)]);
+    }
+
+    #[test]
+    fn epistatic_pairs_drop_odd_key_and_can_be_fewer_than_requested() {
+        assert_eq!(picked_pairs(2, &[30, 10, 20]), vec![(10, 0), (20, 0)]);
+        assert_eq!(picked_pairs(3, &[40, 10, 30, 20]), vec![(10, 0), (20, 0), (30, 1), (40, 1)]);
+        assert_eq!(picked_pairs(1, &[10]), vec![]);
+    }
+
+    fn genotype_sim(dosage: f64) -> Option<GenotypeSim> {
+        let het_prob = if dosage == 1.0 { 1.0 } else { 0.0 };
+        Some(GenotypeSim { dosages: vec![dosage], het_probs: vec![het_prob],
+            n_unknown_alleles: 0, n_known_alleles: 2, is_haploid: false })
+    }
+
+    #[test]
+    fn sex_inferrer_skips_escapes_and_other_contigs() {
+        let escape_file = temp_path("escapes.tsv");
+        std::fs::write(&escape_file, "X\t100\t200\n").unwrap();
+        let compensation =
+            Compensation::new(&[String::from("X=escape")], &Some(escape_file.clone()), &None)
+                .unwrap().unwrap();
+        let mut sex_inferrer = SexInferrer::new(&compensation, DosageField::Genotype);
+        // First sample is male and second female, but all calls in escapes and on other contigs
+        // look like the opposite.
+        for pos in [1000, 2000, 3000, 4000] {
+            let genotype_sims = vec![genotype_sim(0.0), genotype_sim(1.0)];
+            sex_inferrer.process_genotype_sims(&record("X", pos), genotype_sims).unwrap();
+        }
+        for (chrom, pos) in [("X", 100), ("X", 150), ("X", 200), ("1", 150), ("1", 1000)] {
+            let genotype_sims = vec![genotype_sim(1.0), genotype_sim(0.0)];
+            sex_inferrer.process_genotype_sims(&record(chrom, pos), genotype_sims).unwrap();
+        }
+        let sexes = sex_inferrer.into_sexes();
+        assert_eq!(sexes, vec![Sex::Male, Sex::Female]);
+        std::fs::remove_file(&escape_file).unwrap();
+    }
+}
@@ -1,11 +1,14 @@
 use noodles::core::{Position, Region};
 use rand::Rng;
+use crate::seed;
+use crate::seed::MyRng;
 
 const MAX_POS: usize = 1 << 29;
 
 pub(crate) struct RegionIterGen {
     region_size: usize,
     step_size_max: usize,
+    rng: MyRng,
 }
 
 pub(crate) struct RegionIter {
@@ -13,21 +16,24 @@ pub(crate) struct RegionIter {
     cursor: usize,
     region_size: usize,
     step_size_max: usize,
+    rng: MyRng,
 }
 
 impl RegionIterGen {
-    pub(crate) fn new(region_size: usize, step_size_max: usize) -> RegionIterGen {
-        RegionIterGen { region_size, step_size_max }
+    pub(crate) fn new(region_size: usize, step_size_max: usize, rng: MyRng) -> RegionIterGen {
+        RegionIterGen { region_size, step_size_max, rng }
     }
-    pub(crate) fn new_region_iter(&self, chrom: String) -> RegionIter {
-        RegionIter::new(chrom, self.region_size, self.step_size_max)
+    pub(crate) fn new_region_iter(&mut self, chrom: String) -> RegionIter {
+        let rng = seed::new_child_rng(&mut self.rng);
+        RegionIter::new(chrom, self.region_size, self.step_size_max, rng)
     }
 }
 
 impl RegionIter {
-    pub(crate) fn new(chrom: String, region_size: usize, step_size_max: usize) -> RegionIter {
+    pub(crate) fn new(chrom: String, region_size: usize, step_size_max: usize, rng: MyRng)
+                      -> RegionIter {
         let cursor = 1usize;
-        RegionIter { chrom, cursor, region_size, step_size_max }
+        RegionIter { chrom, cursor, region_size, step_size_max, rng }
     }
 }
 
@@ -35,8 +41,7 @@ impl Iterator for RegionIter {
     type Item = Region;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut rng = rand::thread_rng();
-        self.cursor += rng.gen_range(0..self.step_size_max);
+        self.cursor += self.rng.gen_range(0..self.step_size_max);
         let start = Position::try_from(self.cursor).unwrap();
         self.cursor += self.region_size;
         let end = Position::try_from(self.cursor).unwrap();
@@ -1,13 +1,46 @@
 pub(crate) mod pheno_result;
 pub(crate) mod sample_result;
+pub(crate) mod covariates;
 
 use crate::config::RenderConfig;
 use crate::error::Error;
-use crate::{sim, phenotype};
+use crate::{sim, phenotype, seed};
+use crate::render::covariates::Covariates;
 
 pub(crate) fn render(config: &RenderConfig) -> Result<(), Error> {
     let sim = sim::io::read_merge(&config.inputs)?;
-    let phenotypes = phenotype::load::load(&config.phenotype_file)?;
-    let sample_results = sim.render_phenotypes(&phenotypes)?;
-    sim::io::write_results(&sim, &sample_results, &phenotypes, &config.output)
+    let definitions = phenotype::load::load(&config.phenotype_file)?;
+    let phenotypes = &definitions.phenotypes;
+    let seed = seed::pick_seed(config.seed);
+    println!("Using seed {}", seed);
+    let mut rng = seed::new_rng(seed);
+    let sample_ids = sim.sample_ids();
+    let needs_covariates =
+        phenotypes.iter().any(|phenotype| { phenotype.sim.covariates > 0.0 });
+    let covariates = match &config.covariates {
+        Some(file) => { Some(Covariates::read(file, &sample_ids)?) }
+        None if needs_covariates => {
+            let mut covariates_rng = seed::new_rng(seed::derive_seed(seed, &["covariates"]));
+            Some(Covariates::simulate(&sim.sexes(), &mut covariates_rng))
+        }
+        None => { None }
+    };
+    let exposures: Vec<Option<Vec<f64>>> = phenotypes.iter().map(|phenotype| {
+        phenotype.sim.gxe.as_ref().map(|gxe| {
+            let mut exposure_rng =
+                seed::new_rng(seed::derive_seed(seed, &[&phenotype.name, "exposure"]));
+            covariates::simulate_exposure(gxe.exposure, sample_ids.len(), &mut exposure_rng)
+        })
+    }).collect();
+    let sample_results =
+        sim.render_phenotypes(&definitions, covariates.as_ref(), &exposures, &mut rng)?;
+    sim::io::write_results(&sim, &sample_results, phenotypes, seed, &config.output)?;
+    let covariates =
+        covariates::add_exposures(covariates, phenotypes, &exposures, sample_ids.len());
+    if let Some(covariates) = covariates {
+        let covariates_file = covariates::file_next_to(&config.output);
+        covariates.write(&sample_ids, &covariates_file)?;
+        println!("Wrote covariates to {}.", covariates_file);
+    }
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use fs_err::File;
+use rand::Rng;
+use crate::error::Error;
+use crate::seed::MyRng;
+use crate::phenotype::Phenotype;
+use crate::phenotype::pheno_sim::Exposure;
+use crate::compensation::Sex;
+use rand_distr::StandardNormal;
+
+const AGE: &str = "age";
+const SEX: &str = "sex";
+const AGE_MIN: f64 = 40.0;
+const AGE_MAX: f64 = 70.0;
+
+pub(crate) struct Covariates {
+    pub(crate) names: Vec<String>,
+    pub(crate) values: Vec<Vec<f64>>,
+}
+
+impl Covariates {
+    pub(crate) fn read(file: &str, sample_ids: &[String]) -> Result<Covariates, Error> {
+        let mut lines = BufReader::new(File::open(file)?).lines();
+        let header = loop {
+            match lines.next() {
+                None => { return Err(Error::from(format!("No header in {}.", file))) }
+                Some(line) => {
+                    let line = line?;
+                    if !line.starts_with("##") {
+                        break line;
+                    }
+                }
+            }
+        };
+        let names: Vec<String> =
+            header.split('\t').skip(1).map(String::from).collect();
+        let mut values_by_id: HashMap<String, Vec<f64>> = HashMap::new();
+        for line in lines {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts = line.split('\t');
+            let id = String::from(parts.next().unwrap());
+            let values =
+                parts.map(|part| { part.parse::<f64>() }).collect::<Result<Vec<f64>, _>>()?;
+            if values.len() != names.len() {
+                return Err(Error::from(
+                    format!("Expected {} covariates for {} in {}, but got {}.", names.len(),
+                            id, file, values.len())
+                ));
+            }
+            values_by_id.insert(id, values);
+        }
+        let mut values: Vec<Vec<f64>> = Vec::new();
+        for sample_id in sample_ids {
+            match values_by_id.remove(sample_id) {
+                None => {
+                    return Err(Error::from(
+                        format!("No covariates for sample {} in {}.", sample_id, file)
+                    ));
+                }
+                Some(sample_values) => { values.push(sample_values) }
+            }
+        }
+        Ok(Covariates { names, values })
+    }
+    pub(crate) fn simulate(sexes: &[Sex], rng: &mut MyRng) -> Covariates {
+        let names = vec![String::from(AGE), String::from(SEX)];
+        let values: Vec<Vec<f64>> = sexes.iter().map(|sex| {
+            let age = rng.gen_range(AGE_MIN..AGE_MAX).floor();
+            let sex = match sex {
+                Sex::Male => { 1.0 }
+                Sex::Female => { 0.0 }
+                Sex::Unknown => { if rng.gen_bool(0.5) { 1.0 } else { 0.0 } }
+            };
+            vec![age, sex]
+        }).collect();
+        Covariates { names, values }
+    }
+    pub(crate) fn write(&self, sample_ids: &[String], file: &str) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(file)?);
+        writeln!(writer, "#id\t{}", self.names.join("\t"))?;
+        for (sample_id, sample_values) in sample_ids.iter().zip(self.values.iter()) {
+            let values: Vec<String> =
+                sample_values.iter().map(|value| { value.to_string() }).collect();
+            writeln!(writer, "{}\t{}", sample_id, values.join("\t"))?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn simulate_exposure(exposure: Exposure, n_samples: usize, rng: &mut MyRng)
+                                 -> Vec<f64> {
+    (0..n_samples).map(|_| {
+        match exposure {
+            Exposure::Binary(p) => { if rng.gen_bool(p) { 1.0 } else { 0.0 } }
+            Exposure::Normal => { rng.sample(StandardNormal) }
+        }
+    }).collect()
+}
+
+pub(crate) fn add_exposures(covariates: Option<Covariates>, phenotypes: &[Phenotype],
+                            exposures: &[Option<Vec<f64>>], n_samples: usize)
+                            -> Option<Covariates> {
+    if exposures.iter().all(|exposure| { exposure.is_none() }) {
+        return covariates;
+    }
+    let mut covariates = covariates.unwrap_or_else(|| {
+        Covariates { names: Vec::new(), values: vec![Vec::new(); n_samples] }
+    });
+    for (phenotype, exposure) in phenotypes.iter().zip(exposures.iter()) {
+        if let Some(exposure) = exposure {
+            covariates.names.push(format!("{}_exposure", phenotype.name));
+            for (sample_values, value) in covariates.values.iter_mut().zip(exposure.iter()) {
+                sample_values.push(*value);
+            }
+        }
+    }
+    Some(covariates)
+}
+
+pub(crate) fn file_next_to(output: &str) -> String {
+    match output.strip_suffix(".tsv") {
+        None => { format!("{}.covariates.tsv", output) }
+        Some(stem) => { format!("{}.covariates.tsv", stem) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compensation::Sex;
+    use crate::seed;
+    use super::Covariates;
+
+    #[test]
+    fn simulated_sex_matches_known_sex() {
+        let sexes: Vec<Sex> =
+            [Sex::Male, Sex::Female, Sex::Unknown].iter().cycle().take(300).copied().collect();
+        let covariates = Covariates::simulate(&sexes, &mut seed::new_rng(42));
+        assert_eq!(covariates.names, vec!["age", "sex"]);
+        let mut n_unknown_males = 0;
+        for (sex, values) in sexes.iter().zip(covariates.values.iter()) {
+            assert!((40.0..70.0).contains(&values[0]));
+            match sex {
+                Sex::Male => { assert_eq!(values[1], 1.0) }
+                Sex::Female => { assert_eq!(values[1], 0.0) }
+                Sex::Unknown => {
+                    assert!(values[1] == 0.0 || values[1] == 1.0);
+                    if values[1] == 1.0 {
+                        n_unknown_males += 1;
+                    }
+                }
+            }
+        }
+        assert!(n_unknown_males > 20 && n_unknown_males < 80);
+    }
+}
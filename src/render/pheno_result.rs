@@ -5,6 +5,10 @@ pub(crate) enum PhenoResult {
     Quantitative(f64),
     Case,
     Control,
+    Class(usize),
+    Count(u64),
+    Survival(f64, bool),
+    Missing,
 }
 
 impl PhenoResult {
@@ -19,6 +23,18 @@ impl PhenoResult {
             (PhenoResult::Control, Category::Binary(binary)) => {
                 Ok(binary.control.clone())
             }
+            (PhenoResult::Class(i_class), Category::Ordinal(ordinal)) => {
+                Ok(ordinal.labels[*i_class].clone())
+            }
+            (PhenoResult::Count(count), Category::Count(_)) => {
+                Ok(format!("{}", count))
+            }
+            (PhenoResult::Survival(time, event), Category::Survival(_)) => {
+                Ok(format!("{}\t{}", time, if *event { 1 } else { 0 }))
+            }
+            (PhenoResult::Missing, category) => {
+                Ok(vec!["NA"; category.column_names("").len()].join("\t"))
+            }
             _ => {
                 Err(Error::from("Phenotype result is of wrong category."))
             }
@@ -1,26 +1,63 @@
 use crate::config::GcsSampleConfig;
-use crate::{Error, phenotype, tabix};
-use crate::records::SimProcessor;
+use crate::{Error, effects, phenotype, tabix, seed};
+use crate::records::{CausalPicker, SexInferrer, SimProcessor};
+use crate::compensation::{Compensation, Sexes};
 use crate::region_iter::RegionIterGen;
 use crate::sim::Sim;
 use crate::sim;
+use crate::sim::allele_sim::AlleleSimGen;
+use std::sync::Mutex;
 
 pub(crate) fn sample(config: &GcsSampleConfig) -> Result<(), Error> {
     println!("Loading phenotypes from {}", config.phenotype_file);
-    let phenotypes = phenotype::load::load(&config.phenotype_file)?;
+    let definitions = phenotype::load::load(&config.phenotype_file)?;
+    let phenotypes = &definitions.phenotypes;
+    let seed = seed::pick_seed(config.seed);
+    println!("Using seed {}", seed);
     println!("Now processing file {} with index {}.", config.data, config.index);
-    let vcf_header = tabix::read_vcf_header(&config.data)?;
-    let sample_ids: Vec<String> = vcf_header.sample_names().iter().map(String::from).collect();
-    let mut sim = Sim::new(sample_ids, &phenotypes);
+    let sample_ids = tabix::read_variant_header(&config.data)?.sample_ids();
     let data = &config.data;
     let index = &config.index;
-    let mut sim_processor = SimProcessor::new(&mut sim, &phenotypes);
-    let region_iter_gen = RegionIterGen::new(config.region_size, config.step_size_max);
+    let mut causal_picker = CausalPicker::new(phenotypes, seed);
+    if causal_picker.is_needed() {
+        println!("Picking {}.", causal_picker.what_is_picked());
+        let mut region_iter_gen =
+            RegionIterGen::new(config.region_size, config.step_size_max, seed::new_rng(seed));
+        tabix::sample_regions(data, index, &mut causal_picker, &mut region_iter_gen)?;
+    }
+    let allele_sim_gen = AlleleSimGen::new(&definitions, seed, causal_picker.into_picks());
+    let mut sim = Sim::new(sample_ids, phenotypes, seed, allele_sim_gen.picked());
+    let mut compensation =
+        Compensation::new(&config.compensation, &config.escape_list, &config.sex_file)?;
+    if let Some(compensation) = &mut compensation {
+        if compensation.sexes.is_none() {
+            println!("Inferring sex.");
+            let mut sex_inferrer = SexInferrer::new(compensation, config.dosage_field);
+            let mut region_iter_gen =
+                RegionIterGen::new(config.region_size, config.step_size_max,
+                                   seed::new_rng(seed));
+            tabix::sample_regions(data, index, &mut sex_inferrer, &mut region_iter_gen)?;
+            compensation.sexes = Some(Sexes::ByIndex(sex_inferrer.into_sexes()));
+        }
+    }
+    let mut region_iter_gen =
+        RegionIterGen::new(config.region_size, config.step_size_max, seed::new_rng(seed));
+    let effects_writer = effects::create_maybe(&config.effects_out, phenotypes)?.map(Mutex::new);
+    if let Some(compensation) = &compensation {
+        compensation.report_uncompensated(&sim.sample_ids())?;
+    }
+    let mut sim_processor =
+        SimProcessor::new(&mut sim, &allele_sim_gen, effects_writer.as_ref(),
+                          compensation.as_ref(), config.dosage_field)?;
     let n_records =
         tabix::sample_regions(data, index, &mut sim_processor,
-                              &region_iter_gen)?;
+                              &mut region_iter_gen)?;
     println!("Read {} records", n_records);
+    let effects_writer =
+        effects_writer.map(|effects_writer| { effects_writer.into_inner().unwrap() });
+    effects::finish_maybe(effects_writer)?;
+    allele_sim_gen.report_unmatched();
+    allele_sim_gen.report_unpaired();
     sim::io::write(&sim, &config.output)?;
     Ok(())
 }
-
@@ -0,0 +1,40 @@
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+pub(crate) type MyRng = ChaCha8Rng;
+
+pub(crate) fn pick_seed(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(|| { rand::thread_rng().gen() })
+}
+
+pub(crate) fn new_rng(seed: u64) -> MyRng {
+    MyRng::seed_from_u64(seed)
+}
+
+pub(crate) fn new_child_rng(rng: &mut MyRng) -> MyRng {
+    MyRng::seed_from_u64(rng.gen())
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv_1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| { (hash ^ (*byte as u64)).wrapping_mul(FNV_PRIME) })
+}
+
+fn mix(hash: u64) -> u64 {
+    let mut z = hash.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+pub(crate) fn derive_seed(seed: u64, parts: &[&str]) -> u64 {
+    let mut hash = fnv_1a(FNV_OFFSET_BASIS, &seed.to_le_bytes());
+    for part in parts {
+        hash = fnv_1a(hash, part.as_bytes());
+        hash = fnv_1a(hash, &[0xff]);
+    }
+    mix(hash)
+}
@@ -3,36 +3,52 @@ pub(crate) mod genotype_sim;
 pub(crate) mod allele_sim;
 pub(crate) mod io;
 
+use crate::error;
 use crate::error::Error;
 use crate::sim::genotype_sim::GenotypeSim;
 use crate::locus::Locus;
 use crate::sim::sample_sim::SampleSim;
 use crate::sim::allele_sim::AlleleSim;
-use crate::phenotype::Phenotype;
+use crate::phenotype::{Definitions, Phenotype};
+use crate::phenotype::correlation::Correlations;
 use crate::stats::Stats;
-use rand_distr::Normal;
-use rand::prelude::{Distribution, ThreadRng};
+use crate::stats;
+use rand_distr::{Normal, StandardNormal, Gamma, Poisson, Exp, Open01};
+use rand::Rng;
 use crate::render::sample_result::SampleResult;
 use crate::render::pheno_result::PhenoResult;
-use crate::phenotype::pheno_sim::{Category, Binary};
+use crate::render::covariates::Covariates;
+use crate::phenotype::pheno_sim::{Category, Binary, BinaryMode, Ordinal, Count,
+                                  Survival};
+use crate::seed::MyRng;
+use crate::compensation::Sex;
 
 pub(crate) struct Sim {
     phenotype_names: Vec<String>,
     sample_sims: Vec<SampleSim>,
     n_records: u64,
+    seed: Option<u64>,
+    picked: Vec<String>,
 }
 
 impl Sim {
-    pub(crate) fn new(sample_ids: Vec<String>, phenotypes: &[Phenotype]) -> Sim {
-        let phenotype_names: Vec<String> = phenotypes.iter().map(|phenotype| {
+    pub(crate) fn new(sample_ids: Vec<String>, phenotypes: &[Phenotype], seed: u64,
+                      picked: Vec<String>) -> Sim {
+        let mut phenotype_names: Vec<String> = phenotypes.iter().map(|phenotype| {
             String::from(&phenotype.name)
         }).collect();
+        for phenotype in phenotypes {
+            if phenotype.sim.epistasis.is_some() {
+                phenotype_names.push(epi_column_name(&phenotype.name))
+            }
+        }
         let sample_sims: Vec<SampleSim> =
             sample_ids.into_iter().map(|sample_id| {
-                SampleSim::new(sample_id, phenotypes.len())
+                SampleSim::new(sample_id, phenotype_names.len())
             }).collect();
         let n_records = 0u64;
-        Sim { phenotype_names, sample_sims, n_records }
+        let seed = Some(seed);
+        Sim { phenotype_names, sample_sims, n_records, seed, picked }
     }
     pub(crate) fn add_genotype_sim(&mut self, genotype_sim: &Option<GenotypeSim>, i_sample: usize,
                         allele_sims: &[AlleleSim]) {
@@ -46,13 +62,24 @@ impl Sim {
             }
         }
     }
+    pub(crate) fn epi_column(&self, phenotype_name: &str) -> Option<usize> {
+        let column_name = epi_column_name(phenotype_name);
+        self.phenotype_names.iter().position(|name| { *name == column_name })
+    }
+    pub(crate) fn add_epistasis(&mut self, i_column: usize, non_zero_dosages1: &[(usize, f64)],
+                                dosages2: &[f64], effect: f64) {
+        for (i_sample, dosage1) in non_zero_dosages1 {
+            let dosage_product = dosage1 * dosages2[*i_sample];
+            self.sample_sims[*i_sample].effects[i_column] += dosage_product * effect;
+        }
+    }
     pub(crate) fn count_record(&mut self) {
         self.n_records += 1;
     }
     pub(crate) fn try_add(&self, o_sim: &Sim) -> Result<Sim, Error> {
         let Sim {
             phenotype_names: o_phenotype_names,
-            sample_sims: o_sample_sims, n_records: o_n_records
+            sample_sims: o_sample_sims, n_records: o_n_records, seed: o_seed, picked: o_picked
         } = o_sim;
         if self.phenotype_names.len() != o_phenotype_names.len() {
             return Err(Error::from(
@@ -75,7 +102,14 @@ impl Sim {
             sample_sims.push(self_sample_sim.try_add(&o_sample_sims[i])?)
         }
         let n_records = self.n_records + o_n_records;
-        Ok(Sim { phenotype_names, sample_sims, n_records })
+        let seed = if self.seed == *o_seed { self.seed } else { None };
+        let mut picked = self.picked.clone();
+        for o_picked in o_picked {
+            if !picked.contains(o_picked) {
+                picked.push(o_picked.clone());
+            }
+        }
+        Ok(Sim { phenotype_names, sample_sims, n_records, seed, picked })
     }
     pub(crate) fn check_same_size_as_samples<T>(&self, items: &[T], locus: &Locus, item_type: &str)
                                                 -> Result<(), Error> {
@@ -89,11 +123,34 @@ impl Sim {
         }
     }
     pub(crate) fn n_samples(&self) -> usize { self.sample_sims.len() }
+    pub(crate) fn sample_ids(&self) -> Vec<String> {
+        self.sample_sims.iter().map(|sample_sim| { sample_sim.id.clone() }).collect()
+    }
+    pub(crate) fn sexes(&self) -> Vec<Sex> {
+        self.sample_sims.iter().map(|sample_sim| { sample_sim.sex }).collect()
+    }
+    pub(crate) fn set_sexes(&mut self, sexes: &[Sex]) {
+        for (sample_sim, sex) in self.sample_sims.iter_mut().zip(sexes.iter()) {
+            sample_sim.sex = *sex;
+        }
+    }
+    pub(crate) fn picked_phenotypes(&self) -> Vec<&str> {
+        self.picked.iter().map(|picked| { picked.split(':').next().unwrap_or(picked) })
+            .collect()
+    }
     pub(crate) fn n_records(&self) -> u64 {
         self.n_records
     }
     pub(crate) fn create_summary(&self) -> String {
-        format!("{} samples, {} records.", self.n_samples(), self.n_records())
+        match self.seed {
+            None => {
+                format!("{} samples, {} records.", self.n_samples(), self.n_records())
+            }
+            Some(seed) => {
+                format!("{} samples, {} records, seed {}.", self.n_samples(), self.n_records(),
+                        seed)
+            }
+        }
     }
     fn new_env_distributions(&self, phenotypes: &[Phenotype], stats: &Stats)
                              -> Result<Vec<Normal<f64>>, Error> {
@@ -101,27 +158,132 @@ impl Sim {
         let gen_variances = stats.variances();
         for (i, phenotype) in phenotypes.iter().enumerate() {
             let h2 = phenotype.sim.heritability;
-            let std_dev = (gen_variances[i] * (1.0 - h2) / h2).sqrt();
+            let env_fraction =
+                (1.0 - h2 - phenotype.sim.covariates - phenotype.sim.epistasis_fraction()
+                    - phenotype.sim.gxe_fractions()).max(0.0);
+            let std_dev = (gen_variances[i] * env_fraction / h2).sqrt();
             distributions.push(Normal::new(0f64, std_dev)?);
         }
         Ok(distributions)
     }
-    fn new_liabilities(&self, env_distributions: &[Normal<f64>]) -> Vec<Vec<f64>> {
+    fn new_covariate_effects(&self, phenotypes: &[Phenotype], stats: &Stats,
+                             covariates: Option<&Covariates>, rng: &mut MyRng)
+                             -> Result<Vec<Vec<f64>>, Error> {
+        let n_samples = self.sample_sims.len();
+        let mut effects: Vec<Vec<f64>> = vec![vec![0.0; phenotypes.len()]; n_samples];
+        let gen_variances = stats.variances();
+        for (i_pheno, phenotype) in phenotypes.iter().enumerate() {
+            let fraction = phenotype.sim.covariates;
+            if fraction <= 0.0 {
+                continue;
+            }
+            let covariates = error::none_to_error(covariates, "Covariates are needed.")?;
+            let weights: Vec<f64> =
+                covariates.names.iter().map(|_| { rng.sample(StandardNormal) }).collect();
+            let mut scores: Vec<f64> = vec![0.0; n_samples];
+            for (i_covariate, weight) in weights.iter().enumerate() {
+                let column: Vec<f64> = covariates.values.iter().map(|sample_values| {
+                    sample_values[i_covariate]
+                }).collect();
+                let (mean, std_dev) = stats::mean_and_std_dev(&column);
+                if std_dev > 0.0 {
+                    for (score, value) in scores.iter_mut().zip(column.iter()) {
+                        *score += weight * (value - mean) / std_dev;
+                    }
+                }
+            }
+            let (mean, std_dev) = stats::mean_and_std_dev(&scores);
+            let h2 = phenotype.sim.heritability;
+            let target_std_dev = (gen_variances[i_pheno] * fraction / h2).sqrt();
+            if std_dev > 0.0 {
+                for (i_sample, score) in scores.iter().enumerate() {
+                    effects[i_sample][i_pheno] = target_std_dev * (score - mean) / std_dev;
+                }
+            }
+        }
+        Ok(effects)
+    }
+    fn add_epistasis_effects(&self, phenotypes: &[Phenotype], stats: &Stats,
+                             effects: &mut [Vec<f64>]) -> Result<(), Error> {
+        let gen_variances = stats.variances();
+        for (i_pheno, phenotype) in phenotypes.iter().enumerate() {
+            let fraction = phenotype.sim.epistasis_fraction();
+            if fraction <= 0.0 {
+                continue;
+            }
+            let i_column = self.epi_column(&phenotype.name).ok_or_else(|| {
+                Error::from(format!("Liabilities have no epistasis column for {}.",
+                                    phenotype.name))
+            })?;
+            let values: Vec<f64> = self.sample_sims.iter().map(|sample_sim| {
+                sample_sim.effects[i_column]
+            }).collect();
+            let (mean, std_dev) = stats::mean_and_std_dev(&values);
+            let h2 = phenotype.sim.heritability;
+            let target_std_dev = (gen_variances[i_pheno] * fraction / h2).sqrt();
+            if std_dev > 0.0 {
+                for (i_sample, value) in values.iter().enumerate() {
+                    effects[i_sample][i_pheno] += target_std_dev * (value - mean) / std_dev;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn add_gxe_effects(&self, phenotypes: &[Phenotype], stats: &Stats,
+                       exposures: &[Option<Vec<f64>>], effects: &mut [Vec<f64>]) {
+        let gen_variances = stats.variances();
+        for (i_pheno, phenotype) in phenotypes.iter().enumerate() {
+            let (gxe, exposure) = match (&phenotype.sim.gxe, &exposures[i_pheno]) {
+                (Some(gxe), Some(exposure)) => { (gxe, exposure) }
+                _ => { continue; }
+            };
+            let h2 = phenotype.sim.heritability;
+            let exposure = standardized(exposure);
+            let gen_effects: Vec<f64> = self.sample_sims.iter().map(|sample_sim| {
+                sample_sim.effects[i_pheno]
+            }).collect();
+            let gen_effects = standardized(&gen_effects);
+            let products: Vec<f64> =
+                gen_effects.iter().zip(exposure.iter()).map(|(gen_effect, exposure)| {
+                    gen_effect * exposure
+                }).collect();
+            let products = standardized(&products);
+            let gxe_std_dev = (gen_variances[i_pheno] * gxe.gxe_fraction / h2).sqrt();
+            let exposure_std_dev = (gen_variances[i_pheno] * gxe.exposure_fraction / h2).sqrt();
+            for (i_sample, sample_effects) in effects.iter_mut().enumerate() {
+                sample_effects[i_pheno] +=
+                    gxe_std_dev * products[i_sample] + exposure_std_dev * exposure[i_sample];
+            }
+        }
+    }
+    fn new_liabilities(&self, env_distributions: &[Normal<f64>], env_correlations: &Correlations,
+                       component_effects: &[Vec<f64>], rng: &mut MyRng) -> Vec<Vec<f64>> {
         let mut liabilities: Vec<Vec<f64>> = Vec::new();
-        for sample_sim in &self.sample_sims {
+        for (i_sample, sample_sim) in self.sample_sims.iter().enumerate() {
+            let mut env_normals: Vec<f64> =
+                env_distributions.iter().map(|_| { rng.sample(StandardNormal) }).collect();
+            for group in &env_correlations.groups {
+                let independent: Vec<f64> =
+                    group.i_phenos.iter().map(|i_pheno| { env_normals[*i_pheno] }).collect();
+                let correlated = group.correlate(&independent);
+                for (i_member, i_pheno) in group.i_phenos.iter().enumerate() {
+                    env_normals[*i_pheno] = correlated[i_member];
+                }
+            }
             let mut sample_liabilities: Vec<f64> = Vec::new();
-            for (i, gen_effect) in sample_sim.effects.iter().enumerate() {
+            for (i, env_distribution) in env_distributions.iter().enumerate() {
+                let gen_effect = sample_sim.effects[i];
                 let env_effect =
-                    env_distributions[i].sample::<ThreadRng>(&mut rand::thread_rng());
-                let liability = gen_effect + env_effect;
+                    env_distribution.mean() + env_distribution.std_dev() * env_normals[i];
+                let liability = gen_effect + env_effect + component_effects[i_sample][i];
                 sample_liabilities.push(liability);
             };
             liabilities.push(sample_liabilities);
         };
         liabilities
     }
-    fn new_sample_results(&self, liabilities: &[Vec<f64>], phenotypes: &[Phenotype])
-                          -> Vec<SampleResult> {
+    fn new_sample_results(&self, liabilities: &[Vec<f64>], phenotypes: &[Phenotype],
+                          rng: &mut MyRng) -> Result<Vec<SampleResult>, Error> {
         let mut sample_results: Vec<SampleResult> =
             self.sample_sims.iter().map(|sample_sim| {
                 SampleResult::new(sample_sim.id.clone(), Vec::<PhenoResult>::new())
@@ -136,72 +298,540 @@ impl Sim {
                     }
                 }
                 Category::Binary(binary) => {
-                    let Binary { prevalence, .. } = binary;
-                    let n_cases = ((sample_results.len() as f64) * prevalence) as usize;
-                    let n_controls = sample_results.len() - n_cases;
-                    let mut pheno_results: Vec<PhenoResult> = Vec::new();
-                    for _ in 0..n_cases { pheno_results.push(PhenoResult::Case) }
-                    for _ in 0..n_controls { pheno_results.push(PhenoResult::Control) }
-                    let mut sorting_pheno_results = true;
-                    while sorting_pheno_results {
-                        sorting_pheno_results = false;
-                        let mut case_min: Option<(usize, f64)> = None;
-                        let mut control_max: Option<(usize, f64)> = None;
-                        for (i_sample, pheno_result)
-                        in pheno_results.iter().enumerate() {
-                            let liability = liabilities[i_sample][i_pheno];
-                            match pheno_result {
-                                PhenoResult::Quantitative(_) => {}
-                                PhenoResult::Case => {
-                                    match case_min {
-                                        None => { case_min = Some((i_sample, liability)) }
-                                        Some((_, min_liability)) => {
-                                            if liability < min_liability {
-                                                case_min = Some((i_sample, liability))
-                                            }
-                                        }
-                                    }
-                                }
-                                PhenoResult::Control => {
-                                    match control_max {
-                                        None => { control_max = Some((i_sample, liability)) }
-                                        Some((_, max_liability)) => {
-                                            if liability > max_liability {
-                                                control_max = Some((i_sample, liability))
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+                    let Binary { prevalence, mode, ascertainment, .. } = binary;
+                    let mut pheno_results = match mode {
+                        BinaryMode::Rank => {
+                            assign_by_rank(liabilities, i_pheno, *prevalence)
                         }
-                        if let Some((i_case_min, _)) = case_min {
-                            if let Some((i_control_max, _)) = control_max {
-                                if i_case_min < i_control_max {
-                                    pheno_results[i_case_min] = PhenoResult::Control;
-                                    pheno_results[i_control_max] = PhenoResult::Case;
-                                    sorting_pheno_results = true;
-                                }
-                            }
+                        BinaryMode::Threshold => {
+                            assign_by_threshold(liabilities, i_pheno, *prevalence)
                         }
+                    };
+                    if let Some(ascertainment) = ascertainment {
+                        ascertain(&mut pheno_results, *ascertainment, rng);
                     }
                     for (i_sample, pheno_result) in
                     pheno_results.drain(..).enumerate() {
                         sample_results[i_sample].pheno_results.push(pheno_result);
                     }
                 }
+                Category::Ordinal(ordinal) => {
+                    let mut pheno_results = assign_classes(liabilities, i_pheno, ordinal);
+                    for (i_sample, pheno_result) in
+                    pheno_results.drain(..).enumerate() {
+                        sample_results[i_sample].pheno_results.push(pheno_result);
+                    }
+                }
+                Category::Count(count) => {
+                    let mut pheno_results = draw_counts(liabilities, i_pheno, count, rng)?;
+                    for (i_sample, pheno_result) in
+                    pheno_results.drain(..).enumerate() {
+                        sample_results[i_sample].pheno_results.push(pheno_result);
+                    }
+                }
+                Category::Survival(survival) => {
+                    let mut pheno_results =
+                        draw_survival(liabilities, i_pheno, survival, rng)?;
+                    for (i_sample, pheno_result) in
+                    pheno_results.drain(..).enumerate() {
+                        sample_results[i_sample].pheno_results.push(pheno_result);
+                    }
+                }
             }
         }
-        sample_results
+        Ok(sample_results)
     }
-    pub(crate) fn render_phenotypes(&self, phenotypes: &[Phenotype])
-                                        -> Result<Vec<SampleResult>, Error> {
-        let mut stats = Stats::new(phenotypes.len());
+    pub(crate) fn render_phenotypes(&self, definitions: &Definitions,
+                                    covariates: Option<&Covariates>,
+                                    exposures: &[Option<Vec<f64>>], rng: &mut MyRng)
+                                    -> Result<Vec<SampleResult>, Error> {
+        let phenotypes = &definitions.phenotypes;
+        let mut stats = Stats::new(self.phenotype_names.len());
         for sample_sim in &self.sample_sims {
             stats.add(&sample_sim.effects)?;
         }
         let env_distributions = self.new_env_distributions(phenotypes, &stats)?;
-        let liabilities = self.new_liabilities(&env_distributions);
-        let sample_results = self.new_sample_results(&liabilities, phenotypes);
+        let mut component_effects =
+            self.new_covariate_effects(phenotypes, &stats, covariates, rng)?;
+        self.add_epistasis_effects(phenotypes, &stats, &mut component_effects)?;
+        self.add_gxe_effects(phenotypes, &stats, exposures, &mut component_effects);
+        let liabilities =
+            self.new_liabilities(&env_distributions, &definitions.env_correlations,
+                                 &component_effects, rng);
+        let sample_results = self.new_sample_results(&liabilities, phenotypes, rng)?;
         Ok(sample_results)
     }
-}
\ No newline at end of file
+}
+
+fn standardized(values: &[f64]) -> Vec<f64> {
+    let (mean, std_dev) = stats::mean_and_std_dev(values);
+    values.iter().map(|value| {
+        if std_dev > 0.0 { (value - mean) / std_dev } else { 0.0 }
+    }).collect()
+}
+
+fn epi_column_name(phenotype_name: &str) -> String {
+    format!("{}:epi", phenotype_name)
+}
+
+fn assign_by_rank(liabilities: &[Vec<f64>], i_pheno: usize, prevalence: f64) -> Vec<PhenoResult> {
+    let n_samples = liabilities.len();
+    let n_cases = ((n_samples as f64) * prevalence) as usize;
+    let mut i_samples: Vec<usize> = (0..n_samples).collect();
+    if n_cases > 0 && n_cases < n_samples {
+        i_samples.select_nth_unstable_by(n_cases - 1, |i_sample1, i_sample2| {
+            liabilities[*i_sample2][i_pheno].total_cmp(&liabilities[*i_sample1][i_pheno])
+                .then(i_sample1.cmp(i_sample2))
+        });
+    }
+    let mut is_case: Vec<bool> = vec![false; n_samples];
+    for i_sample in &i_samples[0..n_cases] {
+        is_case[*i_sample] = true;
+    }
+    is_case.into_iter().map(|is_case| {
+        if is_case { PhenoResult::Case } else { PhenoResult::Control }
+    }).collect()
+}
+
+fn assign_by_threshold(liabilities: &[Vec<f64>], i_pheno: usize, prevalence: f64)
+                       -> Vec<PhenoResult> {
+    let pheno_liabilities: Vec<f64> =
+        liabilities.iter().map(|sample_liabilities| { sample_liabilities[i_pheno] }).collect();
+    let (mean, std_dev) = stats::mean_and_std_dev(&pheno_liabilities);
+    let z = stats::normal_quantile(1.0 - prevalence);
+    let threshold = if z.is_infinite() { z } else { mean + std_dev * z };
+    pheno_liabilities.iter().map(|liability| {
+        if *liability > threshold { PhenoResult::Case } else { PhenoResult::Control }
+    }).collect()
+}
+
+fn assign_classes(liabilities: &[Vec<f64>], i_pheno: usize, ordinal: &Ordinal)
+                  -> Vec<PhenoResult> {
+    let n_samples = liabilities.len();
+    let mut i_samples: Vec<usize> = (0..n_samples).collect();
+    i_samples.sort_by(|i_sample1, i_sample2| {
+        liabilities[*i_sample1][i_pheno].total_cmp(&liabilities[*i_sample2][i_pheno])
+            .then(i_sample1.cmp(i_sample2))
+    });
+    let mut classes: Vec<usize> = vec![0; n_samples];
+    let mut cumulative = 0.0;
+    let mut i_rank_begin = 0usize;
+    for (i_class, proportion) in ordinal.proportions.iter().enumerate() {
+        cumulative += proportion;
+        let i_rank_end = if i_class == ordinal.proportions.len() - 1 {
+            n_samples
+        } else {
+            (((n_samples as f64) * cumulative).round() as usize).min(n_samples)
+        };
+        for i_sample in &i_samples[i_rank_begin..i_rank_end.max(i_rank_begin)] {
+            classes[*i_sample] = i_class;
+        }
+        i_rank_begin = i_rank_end.max(i_rank_begin);
+    }
+    classes.into_iter().map(PhenoResult::Class).collect()
+}
+
+fn draw_counts(liabilities: &[Vec<f64>], i_pheno: usize, count: &Count, rng: &mut MyRng)
+               -> Result<Vec<PhenoResult>, Error> {
+    let pheno_liabilities: Vec<f64> =
+        liabilities.iter().map(|sample_liabilities| { sample_liabilities[i_pheno] }).collect();
+    let (mean, std_dev) = stats::mean_and_std_dev(&pheno_liabilities);
+    let mut pheno_results: Vec<PhenoResult> = Vec::new();
+    for liability in pheno_liabilities {
+        let standardized = if std_dev > 0.0 { (liability - mean) / std_dev } else { 0.0 };
+        let mut rate = count.rate_for(standardized);
+        if let Some(size) = count.size {
+            rate = rng.sample(Gamma::new(size, rate / size)?);
+        }
+        let n = if rate > 0.0 { rng.sample(Poisson::new(rate)?) as u64 } else { 0 };
+        pheno_results.push(PhenoResult::Count(n))
+    }
+    Ok(pheno_results)
+}
+
+fn draw_survival(liabilities: &[Vec<f64>], i_pheno: usize, survival: &Survival,
+                 rng: &mut MyRng) -> Result<Vec<PhenoResult>, Error> {
+    let pheno_liabilities: Vec<f64> =
+        liabilities.iter().map(|sample_liabilities| { sample_liabilities[i_pheno] }).collect();
+    let (mean, std_dev) = stats::mean_and_std_dev(&pheno_liabilities);
+    let censoring_distribution = survival.random_censoring.map(Exp::new).transpose()?;
+    let mut pheno_results: Vec<PhenoResult> = Vec::new();
+    for liability in pheno_liabilities {
+        let standardized = if std_dev > 0.0 { (liability - mean) / std_dev } else { 0.0 };
+        let event_time = survival.event_time(standardized, rng.sample(Open01));
+        let mut censoring_time = survival.admin_censoring.unwrap_or(f64::INFINITY);
+        if let Some(censoring_distribution) = &censoring_distribution {
+            censoring_time = censoring_time.min(rng.sample(censoring_distribution));
+        }
+        let pheno_result = if event_time <= censoring_time {
+            PhenoResult::Survival(event_time, true)
+        } else {
+            PhenoResult::Survival(censoring_time, false)
+        };
+        pheno_results.push(pheno_result)
+    }
+    Ok(pheno_results)
+}
+
+fn ascertain(pheno_results: &mut [PhenoResult], case_fraction: f64, rng: &mut MyRng) {
+    let mut i_cases: Vec<usize> = Vec::new();
+    let mut i_controls: Vec<usize> = Vec::new();
+    for (i_sample, pheno_result) in pheno_results.iter().enumerate() {
+        match pheno_result {
+            PhenoResult::Case => { i_cases.push(i_sample) }
+            PhenoResult::Control => { i_controls.push(i_sample) }
+            _ => {}
+        }
+    }
+    let control_per_case = (1.0 - case_fraction) / case_fraction;
+    let n_controls_wanted = ((i_cases.len() as f64) * control_per_case).round() as usize;
+    let (n_cases, n_controls) = if n_controls_wanted <= i_controls.len() {
+        (i_cases.len(), n_controls_wanted)
+    } else {
+        let n_cases = ((i_controls.len() as f64) / control_per_case).round() as usize;
+        let n_cases = n_cases.min(i_cases.len());
+        println!("Warning: only {} controls for {} cases, so the study has {} cases and {} \
+        controls.", i_controls.len(), i_cases.len(), n_cases, i_controls.len());
+        (n_cases, i_controls.len())
+    };
+    for pheno_result in pheno_results.iter_mut() {
+        *pheno_result = PhenoResult::Missing;
+    }
+    for i in rand::seq::index::sample(rng, i_cases.len(), n_cases) {
+        pheno_results[i_cases[i]] = PhenoResult::Case;
+    }
+    for i in rand::seq::index::sample(rng, i_controls.len(), n_controls) {
+        pheno_results[i_controls[i]] = PhenoResult::Control;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use std::sync::Mutex;
+    use noodles::vcf::Record;
+    use noodles::vcf::record::{AlternateBases, Chromosome, Position, ReferenceBases};
+    use crate::effects::EffectsWriter;
+    use crate::phenotype::{parse, Definition};
+    use crate::phenotype::load::{ENV_CORRELATION, GEN_CORRELATION};
+    use crate::records::{CausalPicker, RecordProcessor, SimProcessor};
+    use crate::sim::allele_sim::AlleleSimGen;
+    use crate::sim::genotype_sim::DosageField;
+    use crate::test_util::temp_path;
+
+    fn assign_by_swapping(liabilities: &[Vec<f64>], i_pheno: usize, prevalence: f64)
+                          -> Vec<bool> {
+        let n_cases = ((liabilities.len() as f64) * prevalence) as usize;
+        let mut is_case: Vec<bool> =
+            (0..liabilities.len()).map(|i_sample| { i_sample < n_cases }).collect();
+        loop {
+            let mut case_min: Option<(usize, f64)> = None;
+            let mut control_max: Option<(usize, f64)> = None;
+            for (i_sample, is_case) in is_case.iter().enumerate() {
+                let liability = liabilities[i_sample][i_pheno];
+                if *is_case {
+                    if case_min.map(|(_, min)| { liability < min }).unwrap_or(true) {
+                        case_min = Some((i_sample, liability))
+                    }
+                } else if control_max.map(|(_, max)| { liability > max }).unwrap_or(true) {
+                    control_max = Some((i_sample, liability))
+                }
+            }
+            match (case_min, control_max) {
+                (Some((i_case_min, min)), Some((i_control_max, max))) if min < max => {
+                    is_case[i_case_min] = false;
+                    is_case[i_control_max] = true;
+                }
+                _ => { break }
+            }
+        }
+        is_case
+    }
+
+    fn cases(pheno_results: &[PhenoResult]) -> Vec<bool> {
+        pheno_results.iter().map(|pheno_result| {
+            matches!(pheno_result, PhenoResult::Case)
+        }).collect()
+    }
+
+    #[test]
+    fn assign_by_rank_matches_swapping() {
+        let mut rng = MyRng::seed_from_u64(42);
+        for n_samples in [1usize, 2, 10, 101, 500] {
+            for prevalence in [0.0, 0.01, 0.1, 0.5, 0.99, 1.0] {
+                let liabilities: Vec<Vec<f64>> = (0..n_samples).map(|_| {
+                    vec![rng.sample(StandardNormal), rng.gen_range(0..4) as f64]
+                }).collect();
+                for i_pheno in 0..2 {
+                    let expected = assign_by_swapping(&liabilities, i_pheno, prevalence);
+                    let actual = cases(&assign_by_rank(&liabilities, i_pheno, prevalence));
+                    let n_cases = actual.iter().filter(|is_case| { **is_case }).count();
+                    assert_eq!(n_cases, expected.iter().filter(|is_case| { **is_case }).count());
+                    if i_pheno == 0 {
+                        assert_eq!(actual, expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn assign_by_threshold_with_prevalence_zero_or_one() {
+        let liabilities: Vec<Vec<f64>> = vec![vec![1.0, -2.0], vec![1.0, 3.0], vec![1.0, 0.5]];
+        for i_pheno in 0..2 {
+            assert_eq!(cases(&assign_by_threshold(&liabilities, i_pheno, 0.0)), vec![false; 3]);
+            assert_eq!(cases(&assign_by_threshold(&liabilities, i_pheno, 1.0)), vec![true; 3]);
+        }
+        assert_eq!(cases(&assign_by_threshold(&liabilities, 1, 0.5)), vec![false, true, false]);
+    }
+
+    fn epistasis_sim(n_samples: usize) -> (Vec<Phenotype>, Sim) {
+        let phenotypes = match parse::parse("foo=norm(0,1),0.3,epi=pairs(1,0.1)").unwrap() {
+            Definition::Phenotypes(phenotypes) => { phenotypes }
+            _ => { panic!("Expected phenotypes.") }
+        };
+        let sample_ids: Vec<String> =
+            (0..n_samples).map(|i_sample| { format!("s{}", i_sample) }).collect();
+        let sim = Sim::new(sample_ids, &phenotypes, 42, Vec::new());
+        (phenotypes, sim)
+    }
+
+    fn epi_values(sim: &Sim) -> Vec<f64> {
+        let i_column = sim.epi_column("foo").unwrap();
+        sim.sample_sims.iter().map(|sample_sim| { sample_sim.effects[i_column] }).collect()
+    }
+
+    #[test]
+    fn add_epistasis_adds_only_to_carriers_of_first_allele() {
+        let (_, mut sim) = epistasis_sim(4);
+        let i_column = sim.epi_column("foo").unwrap();
+        sim.add_epistasis(i_column, &[(1, 1.0), (2, 2.0), (3, 0.5)], &[1.0, 1.0, 0.0, 2.0], 0.5);
+        assert_eq!(epi_values(&sim), vec![0.0, 0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn epistatic_pair_on_one_multi_allelic_record() {
+        let effects_file = temp_path("multi.tsv");
+        let (phenotypes, mut sim) = epistasis_sim(4);
+        let record =
+            Record::builder().set_chromosome("chr1".parse::<Chromosome>().unwrap())
+                .set_position(Position::from(100))
+                .set_reference_bases("A".parse::<ReferenceBases>().unwrap())
+                .set_alternate_bases("G,T".parse::<AlternateBases>().unwrap())
+                .build().unwrap();
+        let mut causal_picker = CausalPicker::new(&phenotypes, 42);
+        causal_picker.process_record(&record).unwrap();
+        let picks = causal_picker.into_picks();
+        let pairs = picks.epi_pairs[0].as_ref().unwrap();
+        assert_eq!(pairs.values().copied().collect::<Vec<usize>>(), vec![0, 0]);
+        let definitions = Definitions {
+            gen_correlations: Correlations::new(GEN_CORRELATION, &phenotypes, Vec::new()).unwrap(),
+            env_correlations: Correlations::new(ENV_CORRELATION, &phenotypes, Vec::new()).unwrap(),
+            phenotypes,
+        };
+        let allele_sim_gen = AlleleSimGen::new(&definitions, 42, picks);
+        let effects_writer =
+            Mutex::new(EffectsWriter::create(&effects_file, &definitions.phenotypes).unwrap());
+        let genotype_sims: Vec<Option<GenotypeSim>> =
+            [[0.0, 0.0], [1.0, 1.0], [2.0, 0.0], [0.0, 2.0]].iter().map(|dosages| {
+                Some(GenotypeSim { dosages: dosages.to_vec(), het_probs: vec![0.0, 0.0],
+                    n_unknown_alleles: 0, n_known_alleles: 2, is_haploid: false })
+            }).collect();
+        let mut sim_processor =
+            SimProcessor::new(&mut sim, &allele_sim_gen, Some(&effects_writer), None,
+                              DosageField::Genotype).unwrap();
+        sim_processor.process_genotype_sims(&record, genotype_sims).unwrap();
+        effects_writer.into_inner().unwrap().finish().unwrap();
+        let epistasis_file = temp_path("multi.epistasis.tsv");
+        let content = std::fs::read_to_string(&epistasis_file).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let fields: Vec<&str> = lines[1].split('\t').collect();
+        assert_eq!(fields[0..10], ["foo", "0", "chr1", "100", "A", "G", "chr1", "100", "A", "T"]);
+        let effect = fields[10].parse::<f64>().unwrap();
+        assert_eq!(epi_values(&sim), vec![0.0, effect, 0.0, 0.0]);
+        std::fs::remove_file(&effects_file).unwrap();
+        std::fs::remove_file(&epistasis_file).unwrap();
+    }
+
+    fn classes(liabilities: &[f64], ordinal: &Ordinal) -> Vec<usize> {
+        let liabilities: Vec<Vec<f64>> =
+            liabilities.iter().map(|liability| { vec![*liability] }).collect();
+        assign_classes(&liabilities, 0, ordinal).iter().map(|pheno_result| {
+            match pheno_result {
+                PhenoResult::Class(i_class) => { *i_class }
+                _ => { panic!("Expected class.") }
+            }
+        }).collect()
+    }
+
+    fn ordinal(proportions: &[f64]) -> Ordinal {
+        let labels: Vec<String> =
+            (0..proportions.len()).map(|i_class| { i_class.to_string() }).collect();
+        Ordinal::new(proportions.to_vec(), labels).unwrap()
+    }
+
+    #[test]
+    fn assign_classes_rounds_cumulative_proportions() {
+        let liabilities: Vec<f64> = (0..10).map(|i| { i as f64 }).collect();
+        assert_eq!(classes(&liabilities, &ordinal(&[0.25, 0.25, 0.5])),
+                   vec![0, 0, 0, 1, 1, 2, 2, 2, 2, 2]);
+        let third = 1.0 / 3.0;
+        assert_eq!(classes(&liabilities, &ordinal(&[third, third, third])),
+                   vec![0, 0, 0, 1, 1, 1, 1, 2, 2, 2]);
+        let reversed: Vec<f64> = liabilities.iter().rev().copied().collect();
+        assert_eq!(classes(&reversed, &ordinal(&[0.25, 0.25, 0.5])),
+                   vec![2, 2, 2, 2, 2, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn assign_classes_gives_remainder_to_last_class() {
+        let liabilities: Vec<f64> = (0..10).map(|i| { i as f64 }).collect();
+        let short = Ordinal {
+            proportions: vec![0.2, 0.2, 0.2],
+            labels: vec![String::from("a"), String::from("b"), String::from("c")],
+        };
+        assert_eq!(classes(&liabilities, &short), vec![0, 0, 1, 1, 2, 2, 2, 2, 2, 2]);
+        assert_eq!(classes(&liabilities[0..1], &ordinal(&[0.2, 0.2, 0.6])), vec![2]);
+    }
+
+    #[test]
+    fn assign_classes_with_zero_proportions() {
+        let liabilities = [3.0, 1.0, 2.0, 0.0];
+        assert_eq!(classes(&liabilities, &ordinal(&[0.5, 0.0, 0.5])), vec![2, 0, 2, 0]);
+        assert_eq!(classes(&liabilities, &ordinal(&[0.0, 1.0])), vec![1; 4]);
+        assert_eq!(classes(&liabilities, &ordinal(&[1.0, 0.0])), vec![0; 4]);
+        assert_eq!(classes(&liabilities, &ordinal(&[0.0, 0.5, 0.0, 0.5, 0.0])),
+                   vec![3, 1, 3, 1]);
+    }
+
+    #[test]
+    fn assign_classes_breaks_ties_by_sample_order() {
+        let liabilities = [1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+        assert_eq!(classes(&liabilities, &ordinal(&[0.5, 0.5])), vec![0, 0, 1, 1, 0, 1]);
+        assert_eq!(classes(&[0.0; 5], &ordinal(&[0.4, 0.2, 0.4])), vec![0, 0, 1, 2, 2]);
+    }
+
+    fn counts(n_samples: usize, count: &Count, rng: &mut MyRng) -> Vec<f64> {
+        let liabilities: Vec<Vec<f64>> =
+            (0..n_samples).map(|_| { vec![rng.sample(StandardNormal)] }).collect();
+        draw_counts(&liabilities, 0, count, rng).unwrap().iter().map(|pheno_result| {
+            match pheno_result {
+                PhenoResult::Count(n) => { *n as f64 }
+                _ => { panic!("Expected count.") }
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn count_rate_keeps_mean() {
+        let count = Count::new(3.0, 0.0, None).unwrap();
+        for liability in [-3.0, 0.0, 0.5, 4.0] {
+            assert_eq!(count.rate_for(liability), 3.0);
+        }
+        let count = Count::new(3.0, 0.5, None).unwrap();
+        let mut rng = MyRng::seed_from_u64(42);
+        let n = 100000;
+        let rates: Vec<f64> = (0..n).map(|_| { count.rate_for(rng.sample(StandardNormal)) })
+            .collect();
+        let (mean, _) = stats::mean_and_std_dev(&rates);
+        assert!((mean - 3.0).abs() < 0.03, "Mean rate is {}.", mean);
+    }
+
+    #[test]
+    fn draw_counts_keeps_mean() {
+        let mut rng = MyRng::seed_from_u64(42);
+        let n_samples = 20000;
+        for (scale, size) in [(0.0, None), (0.5, None), (0.0, Some(2.0)), (0.5, Some(2.0))] {
+            let count = Count::new(3.0, scale, size).unwrap();
+            let (mean, std_dev) = stats::mean_and_std_dev(&counts(n_samples, &count, &mut rng));
+            assert!((mean - 3.0).abs() < 0.1, "Mean count is {} for scale {} and size {:?}.",
+                    mean, scale, size);
+            if scale == 0.0 {
+                let variance_expected = 3.0 + size.map(|size| { 9.0 / size }).unwrap_or(0.0);
+                let variance = std_dev * std_dev;
+                assert!((variance / variance_expected - 1.0).abs() < 0.1,
+                        "Variance is {} for size {:?}.", variance, size);
+            }
+        }
+    }
+
+    fn survivals(n_samples: usize, survival: &Survival, rng: &mut MyRng) -> Vec<(f64, bool)> {
+        let liabilities: Vec<Vec<f64>> =
+            (0..n_samples).map(|_| { vec![rng.sample(StandardNormal)] }).collect();
+        draw_survival(&liabilities, 0, survival, rng).unwrap().iter().map(|pheno_result| {
+            match pheno_result {
+                PhenoResult::Survival(time, event) => { (*time, *event) }
+                _ => { panic!("Expected survival.") }
+            }
+        }).collect()
+    }
+
+    fn event_fraction(survivals: &[(f64, bool)]) -> f64 {
+        survivals.iter().filter(|(_, event)| { *event }).count() as f64 / survivals.len() as f64
+    }
+
+    #[test]
+    fn survival_event_time() {
+        let survival = Survival::new(0.5, 2.0, 1.0, None, None).unwrap();
+        let uniform = (-2.0f64).exp();
+        assert!((survival.event_time(0.0, uniform) - 2.0).abs() < 1e-12);
+        assert!((survival.event_time(4.0f64.ln(), uniform) - 1.0).abs() < 1e-12);
+        assert!(survival.event_time(1.0, 0.5) < survival.event_time(-1.0, 0.5));
+    }
+
+    #[test]
+    fn draw_survival_censoring() {
+        let mut rng = MyRng::seed_from_u64(42);
+        let n_samples = 20000;
+        let uncensored = Survival::new(1.0, 1.0, 0.0, None, None).unwrap();
+        assert!(survivals(n_samples, &uncensored, &mut rng).iter().all(|(_, event)| { *event }));
+        let admin = Survival::new(1.0, 1.0, 0.0, Some(1.0), None).unwrap();
+        let admin_survivals = survivals(n_samples, &admin, &mut rng);
+        for (time, event) in &admin_survivals {
+            assert!(if *event { *time <= 1.0 } else { *time == 1.0 });
+        }
+        let fraction = event_fraction(&admin_survivals);
+        assert!((fraction - (1.0 - (-1.0f64).exp())).abs() < 0.02, "Events: {}", fraction);
+        let random = Survival::new(1.0, 1.0, 0.0, None, Some(1.0)).unwrap();
+        let random_survivals = survivals(n_samples, &random, &mut rng);
+        let censored_times: Vec<f64> =
+            random_survivals.iter().filter(|(_, event)| { !*event })
+                .map(|(time, _)| { *time }).collect();
+        assert!(censored_times.iter().any(|time| { *time > 1.0 }));
+        assert!(censored_times.iter().any(|time| { *time < 1.0 }));
+        let fraction = event_fraction(&random_survivals);
+        assert!((fraction - 0.5).abs() < 0.02, "Events: {}", fraction);
+        let both = Survival::new(1.0, 1.0, 0.0, Some(1.0), Some(1.0)).unwrap();
+        let both_survivals = survivals(n_samples, &both, &mut rng);
+        assert!(both_survivals.iter().all(|(time, _)| { *time <= 1.0 }));
+        let censored_times: Vec<f64> =
+            both_survivals.iter().filter(|(_, event)| { !*event })
+                .map(|(time, _)| { *time }).collect();
+        assert!(censored_times.iter().any(|time| { *time == 1.0 }));
+        assert!(censored_times.iter().any(|time| { *time < 1.0 }));
+        let fraction = event_fraction(&both_survivals);
+        assert!((fraction - 0.5 * (1.0 - (-2.0f64).exp())).abs() < 0.02, "Events: {}", fraction);
+    }
+
+    #[test]
+    fn survival_has_time_and_event_columns() {
+        let category = Category::Survival(Survival::new(1.0, 1.0, 0.0, None, None).unwrap());
+        assert_eq!(category.column_names("foo"), vec!["foo_time", "foo_event"]);
+        assert_eq!(Category::Quantitative.column_names("foo"), vec!["foo"]);
+        let event = PhenoResult::Survival(1.5, true).to_string_for(&category).unwrap();
+        assert_eq!(event, "1.5\t1");
+        let censored = PhenoResult::Survival(2.0, false).to_string_for(&category).unwrap();
+        assert_eq!(censored, "2\t0");
+        assert_eq!(PhenoResult::Missing.to_string_for(&category).unwrap(), "NA\tNA");
+    }
+
+    #[test]
+    fn assign_by_rank_breaks_ties_by_sample_order() {
+        let liabilities: Vec<Vec<f64>> =
+            [0.0, 1.0, 2.0, 1.0, 1.0, 0.0].iter().map(|liability| { vec![*liability] }).collect();
+        let actual = cases(&assign_by_rank(&liabilities, 0, 0.5));
+        assert_eq!(actual, vec![false, true, true, true, false, false]);
+    }
+}
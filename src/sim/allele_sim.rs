@@ -1,18 +1,249 @@
-use crate::phenotype::Phenotype;
-use rand::prelude::{Distribution, ThreadRng};
+use crate::phenotype::{Definitions, Phenotype};
+use crate::phenotype::correlation::Correlations;
+use rand_distr::StandardNormal;
+use rand::Rng;
+use crate::phenotype::pheno_sim::{Effects, GeneticModel};
+use crate::locus::Allele;
+use crate::seed;
+use crate::records::Picks;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+pub(crate) type NonZeroDosages = Vec<(usize, f64)>;
 
 pub(crate) struct AlleleSim {
     pub(crate) effects: Vec<f64>,
+    pub(crate) dominance: Vec<f64>,
+    pub(crate) epi_slots: Vec<(usize, usize)>,
+}
+
+pub(crate) struct AlleleSimGen<'a> {
+    pub(crate) phenotypes: &'a [Phenotype],
+    pub(crate) seed: u64,
+    gen_correlations: &'a Correlations,
+    gen_group_members: Vec<Option<(usize, usize)>>,
+    causal_thresholds: Vec<Option<u64>>,
+    epi_pairs: Vec<Option<HashMap<u64, usize>>>,
+    epi_buffer: Mutex<HashMap<(usize, usize), (Allele, NonZeroDosages)>>,
+    matched_fixed: Mutex<HashSet<Allele>>,
+}
+
+pub(crate) fn causal_key(allele_seed: u64, phenotype: &Phenotype) -> u64 {
+    seed::derive_seed(allele_seed, &[&phenotype.name, "causal"])
+}
+
+pub(crate) fn epi_key(allele_seed: u64, phenotype: &Phenotype) -> u64 {
+    seed::derive_seed(allele_seed, &[&phenotype.name, "epi"])
 }
 
-impl AlleleSim {
-    pub(crate) fn from_phenotypes(phenotypes: &[Phenotype] ) -> AlleleSim {
+impl AlleleSimGen<'_> {
+    pub(crate) fn new<'a>(definitions: &'a Definitions, seed: u64, picks: Picks)
+                          -> AlleleSimGen<'a> {
+        let phenotypes = &definitions.phenotypes;
+        let gen_correlations = &definitions.gen_correlations;
+        let gen_group_members = gen_correlations.group_members(phenotypes.len());
+        let Picks { causal_thresholds, epi_pairs } = picks;
+        let epi_buffer = Mutex::new(HashMap::new());
+        let matched_fixed = Mutex::new(HashSet::new());
+        AlleleSimGen {
+            phenotypes, seed, gen_correlations, gen_group_members, causal_thresholds,
+            epi_pairs, epi_buffer, matched_fixed,
+        }
+    }
+    fn new_gen_normals(&self, allele_seed: u64) -> Vec<Vec<f64>> {
+        self.gen_correlations.groups.iter().map(|group| {
+            let mut rng =
+                seed::new_rng(seed::derive_seed(allele_seed, &["rg", &group.key]));
+            let independent: Vec<f64> =
+                group.i_phenos.iter().map(|_| { rng.sample(StandardNormal) }).collect();
+            group.correlate(&independent)
+        }).collect()
+    }
+    pub(crate) fn new_allele_sim(&self, allele: &Allele, freq: f64) -> AlleleSim {
+        let allele_seed = allele.derive_seed(self.seed);
+        let gen_normals = self.new_gen_normals(allele_seed);
         let mut effects: Vec<f64> = Vec::new();
-        for phenotype in phenotypes {
-            let effect =
-                phenotype.sim.effect_distribution.sample::<ThreadRng>(&mut rand::thread_rng());
+        for (i_pheno, phenotype) in self.phenotypes.iter().enumerate() {
+            let mut rng =
+                seed::new_rng(seed::derive_seed(allele_seed, &[&phenotype.name]));
+            let normal =
+                self.gen_group_members[i_pheno].map(|(i_group, i_member)| {
+                    gen_normals[i_group][i_member]
+                });
+            let effect = match &phenotype.sim.effects {
+                Effects::Random(distribution) => {
+                    distribution.sample_effect(&mut rng, freq, normal)
+                }
+                Effects::Causal(causal) => {
+                    let threshold = self.causal_thresholds[i_pheno].unwrap_or(u64::MAX);
+                    if causal_key(allele_seed, phenotype) <= threshold {
+                        causal.distribution.sample_effect(&mut rng, freq, normal)
+                    } else {
+                        0.0
+                    }
+                }
+                Effects::Fixed(fixed) => {
+                    match fixed.effects.get(allele) {
+                        None => { 0.0 }
+                        Some(effect) => {
+                            self.matched_fixed.lock().unwrap().insert(allele.clone());
+                            *effect
+                        }
+                    }
+                }
+            };
             effects.push(effect);
         }
-        AlleleSim { effects }
+        let dominance = self.new_dominance(allele_seed, freq, &effects);
+        let epi_slots = self.find_epi_slots(allele_seed);
+        AlleleSim { effects, dominance, epi_slots }
+    }
+    fn new_dominance(&self, allele_seed: u64, freq: f64, effects: &[f64]) -> Vec<f64> {
+        self.phenotypes.iter().zip(effects.iter()).map(|(phenotype, effect)| {
+            match &phenotype.sim.model {
+                GeneticModel::Additive => { 0.0 }
+                GeneticModel::Dominant => { *effect }
+                GeneticModel::Recessive => { -*effect }
+                GeneticModel::Deviation(distribution) => {
+                    if *effect == 0.0 {
+                        0.0
+                    } else {
+                        let mut rng =
+                            seed::new_rng(seed::derive_seed(allele_seed,
+                                                            &[&phenotype.name, "dominance"]));
+                        distribution.sample_effect(&mut rng, freq, None)
+                    }
+                }
+            }
+        }).collect()
+    }
+    fn find_epi_slots(&self, allele_seed: u64) -> Vec<(usize, usize)> {
+        let mut epi_slots: Vec<(usize, usize)> = Vec::new();
+        for (i_pheno, phenotype) in self.phenotypes.iter().enumerate() {
+            if let Some(pairs) = &self.epi_pairs[i_pheno] {
+                if let Some(i_pair) = pairs.get(&epi_key(allele_seed, phenotype)) {
+                    epi_slots.push((i_pheno, *i_pair))
+                }
+            }
+        }
+        epi_slots
+    }
+    pub(crate) fn pair_up(&self, i_pheno: usize, i_pair: usize, allele: &Allele, dosages: &[f64])
+                          -> Option<(Allele, NonZeroDosages, f64)> {
+        let mut epi_buffer = self.epi_buffer.lock().unwrap();
+        match epi_buffer.remove(&(i_pheno, i_pair)) {
+            None => {
+                let non_zero: NonZeroDosages =
+                    dosages.iter().copied().enumerate().filter(|(_, dosage)| { *dosage != 0.0 })
+                        .collect();
+                epi_buffer.insert((i_pheno, i_pair), (allele.clone(), non_zero));
+                None
+            }
+            Some((allele_first, dosages_first)) => {
+                let name = &self.phenotypes[i_pheno].name;
+                let mut rng =
+                    seed::new_rng(seed::derive_seed(self.seed,
+                                                    &[name, "epi", &i_pair.to_string()]));
+                Some((allele_first, dosages_first, rng.sample(StandardNormal)))
+            }
+        }
+    }
+    pub(crate) fn picked(&self) -> Vec<String> {
+        let mut picked: Vec<String> = Vec::new();
+        for (phenotype, threshold) in self.phenotypes.iter().zip(self.causal_thresholds.iter()) {
+            if let (Effects::Causal(_), Some(threshold)) = (&phenotype.sim.effects, threshold) {
+                picked.push(format!("{}:{}:threshold={}", phenotype.name, phenotype.sim.effects,
+                                    threshold));
+            }
+        }
+        for (phenotype, pairs) in self.phenotypes.iter().zip(self.epi_pairs.iter()) {
+            if let (Some(epistasis), Some(_)) = (&phenotype.sim.epistasis, pairs) {
+                picked.push(format!("{}:epi=pairs({},{})", phenotype.name, epistasis.n_pairs,
+                                    epistasis.fraction));
+            }
+        }
+        picked
+    }
+    fn n_unpaired(&self) -> usize {
+        self.epi_buffer.lock().unwrap().len()
+    }
+    pub(crate) fn report_unpaired(&self) {
+        let n_unpaired = self.n_unpaired();
+        if n_unpaired > 0 {
+            println!("Warning: {} epistatic pairs had only one allele in the input.",
+                     n_unpaired);
+        }
+    }
+    pub(crate) fn report_unmatched(&self) {
+        let matched_fixed = self.matched_fixed.lock().unwrap();
+        let mut files_reported: HashSet<&str> = HashSet::new();
+        for phenotype in self.phenotypes {
+            if let Effects::Fixed(fixed) = &phenotype.sim.effects {
+                if !files_reported.insert(&fixed.file) {
+                    continue;
+                }
+                let mut unmatched: Vec<&Allele> =
+                    fixed.effects.keys().filter(|allele| { !matched_fixed.contains(allele) })
+                        .collect();
+                unmatched.sort();
+                println!("{} of {} alleles in {} were not found in the input.",
+                         unmatched.len(), fixed.effects.len(), fixed.file);
+                for allele in unmatched {
+                    println!("Not found: {}", allele);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::locus::{Allele, Locus};
+    use crate::phenotype::{parse, Definition, Definitions};
+    use crate::phenotype::correlation::Correlations;
+    use crate::phenotype::load::{ENV_CORRELATION, GEN_CORRELATION};
+    use crate::records::Picks;
+    use super::AlleleSimGen;
+
+    fn definitions(string: &str) -> Definitions {
+        let phenotypes = match parse::parse(string).unwrap() {
+            Definition::Phenotypes(phenotypes) => { phenotypes }
+            _ => { panic!("Expected phenotypes.") }
+        };
+        let gen_correlations =
+            Correlations::new(GEN_CORRELATION, &phenotypes, Vec::new()).unwrap();
+        let env_correlations =
+            Correlations::new(ENV_CORRELATION, &phenotypes, Vec::new()).unwrap();
+        Definitions { phenotypes, gen_correlations, env_correlations }
+    }
+
+    fn allele(pos: usize) -> Allele {
+        Allele::new(Locus::from_parts("chr1", pos), String::from("A"), String::from("G"))
+    }
+
+    #[test]
+    fn pair_up_buffers_first_allele_and_keeps_only_its_carriers() {
+        let definitions = definitions("foo=norm(0,1),0.3,epi=pairs(2,0.1)");
+        let epi_pairs: HashMap<u64, usize> = HashMap::from([(10, 0), (20, 0), (30, 1), (40, 1)]);
+        let picks = Picks { causal_thresholds: vec![None], epi_pairs: vec![Some(epi_pairs)] };
+        let allele_sim_gen = AlleleSimGen::new(&definitions, 42, picks);
+        assert!(allele_sim_gen.pair_up(0, 0, &allele(100), &[0.0, 1.0, 2.0, 0.5]).is_none());
+        assert!(allele_sim_gen.pair_up(0, 1, &allele(150), &[1.0, 0.0, 0.0, 0.0]).is_none());
+        assert_eq!(allele_sim_gen.n_unpaired(), 2);
+        let (allele_first, dosages_first, effect) =
+            allele_sim_gen.pair_up(0, 0, &allele(200), &[1.0, 1.0, 0.0, 2.0]).unwrap();
+        assert!(allele_first == allele(100));
+        assert_eq!(dosages_first, vec![(1, 1.0), (2, 2.0), (3, 0.5)]);
+        assert!(effect.is_finite() && effect != 0.0);
+        assert_eq!(allele_sim_gen.n_unpaired(), 1);
+        let allele_sim_gen = AlleleSimGen::new(&definitions, 42, Picks {
+            causal_thresholds: vec![None], epi_pairs: vec![Some(HashMap::new())]
+        });
+        allele_sim_gen.pair_up(0, 0, &allele(300), &[1.0; 4]);
+        let (_, _, effect_again) =
+            allele_sim_gen.pair_up(0, 0, &allele(400), &[1.0; 4]).unwrap();
+        assert_eq!(effect_again, effect);
+        assert_eq!(allele_sim_gen.n_unpaired(), 0);
     }
-}
\ No newline at end of file
+}
@@ -1,24 +1,301 @@
+use noodles::vcf::header::format::Key;
+use noodles::vcf::Record;
 use noodles::vcf::record::genotype::field::value::Genotype;
+use noodles::vcf::record::genotypes::genotype::field::Value;
+use crate::error::Error;
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum DosageField {
+    Genotype,
+    Dosage,
+    Probabilities,
+}
 
 pub(crate) struct GenotypeSim {
-    pub(crate) dosages: Vec<u8>,
-    pub(crate) n_unknown_alleles: u64
+    pub(crate) dosages: Vec<f64>,
+    pub(crate) het_probs: Vec<f64>,
+    pub(crate) n_unknown_alleles: u64,
+    pub(crate) n_known_alleles: u64,
+    pub(crate) is_haploid: bool,
+}
+
+impl DosageField {
+    pub(crate) fn parse(string: &str) -> Result<DosageField, Error> {
+        match string {
+            "GT" => { Ok(DosageField::Genotype) }
+            "DS" => { Ok(DosageField::Dosage) }
+            "GP" => { Ok(DosageField::Probabilities) }
+            _ => {
+                Err(Error::from(
+                    format!("Dosage field needs to be GT, DS or GP, but got '{}'.", string)
+                ))
+            }
+        }
+    }
+    pub(crate) fn genotype_sims(&self, record: &Record)
+                                -> Result<Vec<Option<GenotypeSim>>, Error> {
+        let n_alt = record.alternate_bases().len();
+        match self {
+            DosageField::Genotype => {
+                let genotypes = record.genotypes().genotypes()?;
+                Ok(genotypes.iter().map(|genotype| {
+                    genotype.as_ref().map(|genotype| { GenotypeSim::new(genotype, n_alt) })
+                }).collect())
+            }
+            DosageField::Dosage | DosageField::Probabilities => {
+                let key = match self {
+                    DosageField::Dosage => { Key::Other(String::from("DS")) }
+                    _ => { Key::GenotypePosteriorProbabilities }
+                };
+                let mut genotype_sims: Vec<Option<GenotypeSim>> = Vec::new();
+                for genotype in record.genotypes().iter() {
+                    let values =
+                        genotype.get(&key).and_then(|field| { field.value() })
+                            .and_then(float_values);
+                    let genotype_sim = match values {
+                        None => { None }
+                        Some(values) if *self == DosageField::Dosage => {
+                            let ploidy = match genotype.genotype().transpose()? {
+                                None => { 2 }
+                                Some(gt) => { gt.len() }
+                            };
+                            GenotypeSim::from_dosages(&values, ploidy, n_alt)?
+                        }
+                        Some(values) => { GenotypeSim::from_probabilities(&values, n_alt)? }
+                    };
+                    genotype_sims.push(genotype_sim);
+                }
+                Ok(genotype_sims)
+            }
+        }
+    }
 }
 
 impl GenotypeSim {
     pub(crate) fn new(genotype: &Genotype, n_alt: usize) -> GenotypeSim {
-        let mut dosages: Vec<u8> = vec![0; n_alt];
+        let mut dosages: Vec<f64> = vec![0.0; n_alt];
         let mut n_unknown_alleles: u64 = 0;
+        let mut n_known_alleles: u64 = 0;
         for allele in genotype.iter() {
             if let Some(pos) = allele.position() {
                 if pos > 0 {
                     let i = pos - 1;
-                    dosages[i] += 1;
+                    dosages[i] += 1.0;
                 }
+                n_known_alleles += 1;
             } else {
                 n_unknown_alleles += 1;
             }
         }
-        GenotypeSim { dosages, n_unknown_alleles }
+        let is_haploid = n_known_alleles + n_unknown_alleles == 1;
+        let het_probs: Vec<f64> =
+            dosages.iter().map(|dosage| {
+                if *dosage == 1.0 && !is_haploid { 1.0 } else { 0.0 }
+            }).collect();
+        GenotypeSim { dosages, het_probs, n_unknown_alleles, n_known_alleles, is_haploid }
+    }
+    fn from_dosages(values: &[Option<f64>], ploidy: usize, n_alt: usize)
+                    -> Result<Option<GenotypeSim>, Error> {
+        if values.len() != n_alt {
+            return Err(Error::from(
+                format!("Expected {} dosages, but got {}.", n_alt, values.len())
+            ));
+        }
+        let dosages = match values.iter().copied().collect::<Option<Vec<f64>>>() {
+            None => { return Ok(None) }
+            Some(dosages) => { dosages }
+        };
+        let is_haploid = ploidy == 1;
+        let het_probs: Vec<f64> =
+            dosages.iter().map(|dosage| {
+                if is_haploid { 0.0 } else { dosage.min(2.0 - dosage).max(0.0) }
+            }).collect();
+        let n_known_alleles = ploidy as u64;
+        Ok(Some(GenotypeSim { dosages, het_probs, n_unknown_alleles: 0, n_known_alleles,
+            is_haploid }))
+    }
+    fn from_probabilities(values: &[Option<f64>], n_alt: usize)
+                          -> Result<Option<GenotypeSim>, Error> {
+        let n_alleles = n_alt + 1;
+        let n_diploid = n_alleles * (n_alleles + 1) / 2;
+        let is_haploid = values.len() == n_alleles;
+        if !is_haploid && values.len() != n_diploid {
+            return Err(Error::from(
+                format!("Expected {} or {} genotype probabilities, but got {}.", n_alleles,
+                        n_diploid, values.len())
+            ));
+        }
+        let probs = match values.iter().copied().collect::<Option<Vec<f64>>>() {
+            None => { return Ok(None) }
+            Some(probs) => { probs }
+        };
+        let mut dosages: Vec<f64> = vec![0.0; n_alt];
+        let mut het_probs: Vec<f64> = vec![0.0; n_alt];
+        if is_haploid {
+            for (i_alt, dosage) in dosages.iter_mut().enumerate() {
+                *dosage = probs[i_alt + 1];
+            }
+        } else {
+            for k in 0..n_alleles {
+                for j in 0..=k {
+                    let prob = probs[k * (k + 1) / 2 + j];
+                    if j > 0 {
+                        dosages[j - 1] += prob;
+                    }
+                    if k > 0 {
+                        dosages[k - 1] += prob;
+                    }
+                    if j != k {
+                        if j > 0 {
+                            het_probs[j - 1] += prob;
+                        }
+                        het_probs[k - 1] += prob;
+                    }
+                }
+            }
+        }
+        let n_known_alleles = if is_haploid { 1 } else { 2 };
+        Ok(Some(GenotypeSim { dosages, het_probs, n_unknown_alleles: 0, n_known_alleles,
+            is_haploid }))
+    }
+    pub(crate) fn het_prob(&self, i_alt: usize) -> f64 {
+        if self.is_haploid { 0.0 } else { self.het_probs[i_alt] }
+    }
+    pub(crate) fn is_heterozygous(&self) -> bool {
+        self.n_known_alleles == 2 && self.het_probs.iter().any(|het_prob| { *het_prob > 0.5 })
+    }
+    pub(crate) fn make_haploid(&mut self, doubled: bool) {
+        for dosage in self.dosages.iter_mut() {
+            if !self.is_haploid {
+                *dosage = dosage.min(1.0);
+            }
+            if doubled {
+                *dosage *= 2.0;
+            }
+        }
+        self.is_haploid = true;
+    }
+}
+
+fn float_values(value: &Value) -> Option<Vec<Option<f64>>> {
+    match value {
+        Value::Float(value) => { Some(vec![Some(*value as f64)]) }
+        Value::FloatArray(values) => {
+            Some(values.iter().map(|value| { value.map(|value| { value as f64 }) }).collect())
+        }
+        Value::Integer(value) => { Some(vec![Some(*value as f64)]) }
+        Value::IntegerArray(values) => {
+            Some(values.iter().map(|value| { value.map(|value| { value as f64 }) }).collect())
+        }
+        _ => { None }
+    }
+}
+
+pub(crate) fn allele_frequencies(genotype_sims: &[Option<GenotypeSim>], n_alt: usize) -> Vec<f64> {
+    let mut alt_counts: Vec<f64> = vec![0.0; n_alt];
+    let mut n_known_alleles: u64 = 0;
+    for genotype_sim in genotype_sims.iter().flatten() {
+        for (i_alt, dosage) in genotype_sim.dosages.iter().enumerate() {
+            alt_counts[i_alt] += *dosage;
+        }
+        n_known_alleles += genotype_sim.n_known_alleles;
     }
-}
\ No newline at end of file
+    alt_counts.iter().map(|alt_count| {
+        if n_known_alleles > 0 { alt_count / (n_known_alleles as f64) } else { 0.0 }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use noodles::vcf::{Header, Record};
+    use super::{DosageField, GenotypeSim};
+
+    const HEADER: &str = "##fileformat=VCFv4.3
+##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">
+##FORMAT=<ID=DS,Number=A,Type=Float,Description=\"Dosage\">
+##FORMAT=<ID=GP,Number=G,Type=Float,Description=\"Genotype probabilities\">
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\ts2\ts3\ts4
+";
+
+    fn genotype_sims(field: &str, alt: &str, samples: &str)
+                     -> Result<Vec<Option<GenotypeSim>>, crate::error::Error> {
+        let header: Header = HEADER.parse().unwrap();
+        let line = format!("1\t100\t.\tA\t{}\t.\t.\t.\tGT:DS:GP\t{}", alt,
+                           samples.replace(' ', "\t"));
+        let record = Record::try_from_str(&line, &header).unwrap();
+        DosageField::parse(field).unwrap().genotype_sims(&record)
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() < 1e-6, "Expected {:?}, but got {:?}.", expected,
+                    actual);
+        }
+    }
+
+    fn assert_sim(genotype_sim: &Option<GenotypeSim>, dosages: &[f64], het_probs: &[f64],
+                  n_known_alleles: u64, is_haploid: bool) {
+        let genotype_sim = genotype_sim.as_ref().unwrap();
+        assert_close(&genotype_sim.dosages, dosages);
+        assert_close(&genotype_sim.het_probs, het_probs);
+        assert_eq!(genotype_sim.n_known_alleles, n_known_alleles);
+        assert_eq!(genotype_sim.is_haploid, is_haploid);
+    }
+
+    #[test]
+    fn genotypes_from_gt() {
+        let sims =
+            genotype_sims("GT", "G", "0/1:.:. 1|1:.:. ./1:.:. 1:.:.").unwrap();
+        assert_sim(&sims[0], &[1.0], &[1.0], 2, false);
+        assert_sim(&sims[1], &[2.0], &[0.0], 2, false);
+        assert_sim(&sims[2], &[1.0], &[1.0], 1, false);
+        assert_eq!(sims[2].as_ref().unwrap().n_unknown_alleles, 1);
+        assert_sim(&sims[3], &[1.0], &[0.0], 1, true);
+    }
+
+    #[test]
+    fn genotypes_from_ds() {
+        let sims =
+            genotype_sims("DS", "G", "0/1:0.9:. 1:0.7:. ./.:.:. 0/0:1.5:.").unwrap();
+        assert_sim(&sims[0], &[0.9], &[0.9], 2, false);
+        assert_sim(&sims[1], &[0.7], &[0.0], 1, true);
+        assert!(sims[2].is_none());
+        assert_sim(&sims[3], &[1.5], &[0.5], 2, false);
+    }
+
+    #[test]
+    fn genotypes_from_gp() {
+        let sims = genotype_sims(
+            "GP", "G", "0/1:.:0.1,0.8,0.1 1:.:0.3,0.7 0/0:.:0,0,1 ./.:.:0.5,0.25,0.25"
+        ).unwrap();
+        assert_sim(&sims[0], &[1.0], &[0.8], 2, false);
+        assert_sim(&sims[1], &[0.7], &[0.0], 1, true);
+        assert_sim(&sims[2], &[2.0], &[0.0], 2, false);
+        assert_sim(&sims[3], &[0.75], &[0.25], 2, false);
+    }
+
+    #[test]
+    fn genotypes_from_gp_with_multiple_alternate_alleles() {
+        let sims = genotype_sims(
+            "GP", "G,T", "0/1:.:0.1,0.2,0.3,0.1,0.2,0.1 0:.:0.2,0.5,0.3 0/0:.:. 0/0:.:."
+        ).unwrap();
+        assert_sim(&sims[0], &[1.0, 0.5], &[0.4, 0.3], 2, false);
+        assert_sim(&sims[1], &[0.5, 0.3], &[0.0, 0.0], 1, true);
+    }
+
+    #[test]
+    fn genotypes_from_missing_gp() {
+        let sims =
+            genotype_sims("GP", "G", "0/1:.:. 0/1:.:0.2,.,0.3 0/1:.:.,.,. 0/1:.:0,1,0").unwrap();
+        assert!(sims[0].is_none());
+        assert!(sims[1].is_none());
+        assert!(sims[2].is_none());
+        assert_sim(&sims[3], &[1.0], &[1.0], 2, false);
+    }
+
+    #[test]
+    fn genotypes_from_gp_with_wrong_count_is_an_error() {
+        assert!(genotype_sims("GP", "G", "0/1:.:0.5,0.5,0,0 0/0:.:. 0/0:.:. 0/0:.:.").is_err());
+    }
+}
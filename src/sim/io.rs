@@ -7,14 +7,30 @@ use crate::sim::sample_sim::SampleSim;
 use crate::sim;
 use crate::render::sample_result::SampleResult;
 use crate::phenotype::Phenotype;
+use crate::compensation::Sex;
 
 const N_RECORDS: &str = "n_records";
+const SEED: &str = "seed";
+const LIABILITIES_SEED: &str = "liabilities_seed";
+const PICKED: &str = "picked";
+const SEXES: &str = "sexes";
 const HEADER_PREFIX: &str = "#id\tn_no_gt\tn_no_alt\t";
 
 pub(crate) fn write(sim: &Sim, file: &str) -> Result<(), Error> {
     let mut writer = BufWriter::new(File::create(file)?);
     writeln!(writer, "##version={}", env!("CARGO_PKG_VERSION"))?;
     writeln!(writer, "##{}={}", N_RECORDS, sim.n_records)?;
+    if let Some(seed) = sim.seed {
+        writeln!(writer, "##{}={}", SEED, seed)?;
+    }
+    for picked in &sim.picked {
+        writeln!(writer, "##{}={}", PICKED, picked)?;
+    }
+    let sexes = sim.sexes();
+    if sexes.iter().any(|sex| { *sex != Sex::Unknown }) {
+        let codes: String = sexes.iter().map(|sex| { sex.code() }).collect();
+        writeln!(writer, "##{}={}", SEXES, codes)?;
+    }
     writeln!(writer, "##n_samples={}", sim.sample_sims.len())?;
     writeln!(writer, "##n_phenotypes={}", sim.phenotype_names.len())?;
     let phenotypes = sim.phenotype_names.join("\t");
@@ -30,15 +46,21 @@ pub(crate) fn write(sim: &Sim, file: &str) -> Result<(), Error> {
 }
 
 pub(crate) fn write_results(sim: &Sim, sample_results: &[SampleResult], phenotypes: &[Phenotype],
-                            file: &str)
+                            seed: u64, file: &str)
     -> Result<(), Error> {
     let mut writer = BufWriter::new(File::create(file)?);
     writeln!(writer, "##version={}", env!("CARGO_PKG_VERSION"))?;
     writeln!(writer, "##{}={}", N_RECORDS, sim.n_records)?;
+    if let Some(liabilities_seed) = sim.seed {
+        writeln!(writer, "##{}={}", LIABILITIES_SEED, liabilities_seed)?;
+    }
+    writeln!(writer, "##{}={}", SEED, seed)?;
     writeln!(writer, "##n_samples={}", sim.sample_sims.len())?;
-    writeln!(writer, "##n_phenotypes={}", sim.phenotype_names.len())?;
-    let phenotypes_names = sim.phenotype_names.join("\t");
-    writeln!(writer, "#id\t{}", phenotypes_names)?;
+    writeln!(writer, "##n_phenotypes={}", phenotypes.len())?;
+    let column_names: Vec<String> = phenotypes.iter().flat_map(|phenotype| {
+        phenotype.sim.category.column_names(&phenotype.name)
+    }).collect();
+    writeln!(writer, "#id\t{}", column_names.join("\t"))?;
     for sample_result in sample_results {
         let mut pheno_strings: Vec<String> = Vec::new();
         for (i_pheno, pheno_result)
@@ -73,6 +95,9 @@ fn ensure_u64_at(value: Option<&str>, id: &str) -> Result<u64, Error> {
 pub(crate) fn read(file: &str) -> Result<Sim, Error> {
     let lines = BufReader::new(File::open(file)?).lines();
     let mut n_records: Option<u64> = None;
+    let mut seed: Option<u64> = None;
+    let mut picked: Vec<String> = Vec::new();
+    let mut sexes: Option<Vec<Sex>> = None;
     let mut phenotype_names: Option<Vec<String>> = None;
     let mut sample_sims: Vec<SampleSim> = Vec::new();
     for line in lines {
@@ -83,6 +108,13 @@ pub(crate) fn read(file: &str) -> Result<Sim, Error> {
                 let value = &line[(eq_pos + 1)..];
                 if field == N_RECORDS {
                     n_records = Some(value.parse()?)
+                } else if field == SEED {
+                    seed = Some(value.parse()?)
+                } else if field == PICKED {
+                    picked.push(String::from(value))
+                } else if field == SEXES {
+                    sexes = Some(value.chars().map(|code| { Sex::parse(&code.to_string()) })
+                        .collect::<Result<Vec<Sex>, Error>>()?)
                 }
             }
         } else if let Some(line) = line.strip_prefix(HEADER_PREFIX) {
@@ -104,7 +136,9 @@ pub(crate) fn read(file: &str) -> Result<Sim, Error> {
                             effects.len(), phenotype_names.len())
                 ));
             }
-            let sample_sim = SampleSim { id, effects, n_unknown_genotypes, n_unknown_alleles };
+            let sample_sim =
+                SampleSim { id, effects, n_unknown_genotypes, n_unknown_alleles,
+                    sex: Sex::Unknown };
             sample_sims.push(sample_sim);
         } else {
             return Err(Error::from("Missing header line"));
@@ -112,7 +146,17 @@ pub(crate) fn read(file: &str) -> Result<Sim, Error> {
     }
     let n_records = n_records.ok_or_else(|| { missing(N_RECORDS) })?;
     let phenotype_names = phenotype_names.ok_or_else(|| { missing(N_RECORDS) })?;
-    Ok(Sim { phenotype_names, sample_sims, n_records })
+    let mut sim = Sim { phenotype_names, sample_sims, n_records, seed, picked };
+    if let Some(sexes) = sexes {
+        if sexes.len() != sim.n_samples() {
+            return Err(Error::from(
+                format!("Got sexes of {} samples, but {} samples.", sexes.len(),
+                        sim.n_samples())
+            ));
+        }
+        sim.set_sexes(&sexes);
+    }
+    Ok(sim)
 }
 
 pub(crate) fn read_merge(inputs: &[String]) -> Result<Sim, Error> {
@@ -125,14 +169,77 @@ pub(crate) fn read_merge(inputs: &[String]) -> Result<Sim, Error> {
             println!("Next reading {}", input);
             let mut sim_all = sim::io::read(input)?;
             println!("File: {}", sim_all.create_summary());
+            let first_input = input;
+            let first_seed = sim_all.seed;
             for input in inputs_iter {
                 println!("Next reading {}", input);
                 let sim_input = sim::io::read(input)?;
                 println!("File: {}", sim_input.create_summary());
+                let picked_all = sim_all.picked_phenotypes();
+                let picked_twice: Vec<&str> =
+                    sim_input.picked_phenotypes().into_iter().filter(|phenotype| {
+                        picked_all.contains(phenotype)
+                    }).collect();
+                if !picked_twice.is_empty() {
+                    return Err(Error::from(
+                        format!("Cannot merge {}, because {} picked causal alleles or \
+                        epistatic pairs in more than one run, so the merged liabilities would \
+                        have too many. Instead, run vcf once with all inputs.", input,
+                                picked_twice.join(", "))
+                    ));
+                }
+                if sim_input.seed != first_seed {
+                    println!("Warning: liabilities in {} and {} were created with different \
+                    seeds, so allele effects are not consistent across files.", first_input,
+                             input);
+                }
                 sim_all = sim_all.try_add(&sim_input)?;
                 println!("All : {}", sim_all.create_summary());
             }
             Ok(sim_all)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compensation::Sex;
+    use crate::phenotype::{parse, Definition};
+    use crate::sim::Sim;
+    use crate::test_util::temp_path;
+
+    fn sim_with_sexes(sexes: &[Sex]) -> Sim {
+        let sample_ids: Vec<String> =
+            (0..sexes.len()).map(|i_sample| { format!("s{}", i_sample) }).collect();
+        let phenotypes = match parse::parse("foo=norm(0,1),0.3").unwrap() {
+            Definition::Phenotypes(phenotypes) => { phenotypes }
+            _ => { panic!("Expected phenotypes.") }
+        };
+        let mut sim = Sim::new(sample_ids, &phenotypes, 42, Vec::new());
+        sim.set_sexes(sexes);
+        sim
+    }
+
+    #[test]
+    fn sexes_are_written_and_read() {
+        let file = temp_path("sexes.tsv");
+        let sexes = vec![Sex::Male, Sex::Unknown, Sex::Female];
+        super::write(&sim_with_sexes(&sexes), &file).unwrap();
+        assert!(std::fs::read_to_string(&file).unwrap().contains("\n##sexes=M0F\n"));
+        assert_eq!(super::read(&file).unwrap().sexes(), sexes);
+        super::write(&sim_with_sexes(&[Sex::Unknown; 3]), &file).unwrap();
+        assert!(!std::fs::read_to_string(&file).unwrap().contains("##sexes="));
+        assert_eq!(super::read(&file).unwrap().sexes(), vec![Sex::Unknown; 3]);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn sexes_are_merged() {
+        let sim1 = sim_with_sexes(&[Sex::Male, Sex::Unknown, Sex::Female]);
+        let sim2 = sim_with_sexes(&[Sex::Male, Sex::Female, Sex::Unknown]);
+        let merged = sim1.try_add(&sim2).unwrap();
+        assert_eq!(merged.sexes(), vec![Sex::Male, Sex::Female, Sex::Female]);
+        let sim3 = sim_with_sexes(&[Sex::Female, Sex::Unknown, Sex::Unknown]);
+        assert!(sim1.try_add(&sim3).is_err());
+    }
+}
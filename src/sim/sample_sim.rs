@@ -1,12 +1,14 @@
 use crate::sim::genotype_sim::GenotypeSim;
 use crate::sim::allele_sim::AlleleSim;
 use crate::error::Error;
+use crate::compensation::Sex;
 
 pub(crate) struct SampleSim {
     pub(crate) id: String,
     pub(crate) effects: Vec<f64>,
     pub(crate) n_unknown_genotypes: u64,
     pub(crate) n_unknown_alleles: u64,
+    pub(crate) sex: Sex,
 }
 
 impl SampleSim {
@@ -14,7 +16,8 @@ impl SampleSim {
         let n_unknown_genotypes = 0u64;
         let n_unknown_alleles = 0u64;
         let effects: Vec<f64> = vec![0f64; n_phenotypes];
-        SampleSim { id, effects, n_unknown_genotypes, n_unknown_alleles }
+        let sex = Sex::Unknown;
+        SampleSim { id, effects, n_unknown_genotypes, n_unknown_alleles, sex }
     }
     pub(crate) fn try_add(&self, o_sample_sim: &SampleSim) -> Result<SampleSim, Error> {
         if self.id.as_str().ne(o_sample_sim.id.as_str()) {
@@ -34,7 +37,17 @@ impl SampleSim {
             }).collect();
         let n_unknown_genotypes = self.n_unknown_genotypes + o_sample_sim.n_unknown_genotypes;
         let n_unknown_alleles = self.n_unknown_alleles + o_sample_sim.n_unknown_alleles;
-        Ok(SampleSim { id, effects, n_unknown_genotypes, n_unknown_alleles })
+        let sex = match (self.sex, o_sample_sim.sex) {
+            (Sex::Unknown, sex) => { sex }
+            (sex, Sex::Unknown) => { sex }
+            (sex, o_sex) if sex == o_sex => { sex }
+            _ => {
+                return Err(Error::from(
+                    format!("Sample {} has sex {} in one input, but {} in another.", id,
+                            self.sex.code(), o_sample_sim.sex.code())));
+            }
+        };
+        Ok(SampleSim { id, effects, n_unknown_genotypes, n_unknown_alleles, sex })
     }
     pub(crate) fn add_unknown_genotype(&mut self) {
         self.n_unknown_genotypes += 1;
@@ -42,8 +55,9 @@ impl SampleSim {
     pub(crate) fn add_allele_effects(&mut self, genotype: &GenotypeSim, allele: &AlleleSim,
                                      i_allele: usize) {
         let dosage = genotype.dosages[i_allele];
+        let het_prob = genotype.het_prob(i_allele);
         for (i, effect) in allele.effects.iter().enumerate() {
-            self.effects[i] += (dosage as f64) * effect;
+            self.effects[i] += dosage * effect + het_prob * allele.dominance[i];
         }
         self.n_unknown_alleles += genotype.n_unknown_alleles;
     }
@@ -0,0 +1,52 @@
+use std::io::{Read, Seek, SeekFrom};
+use fs_err::File;
+use crate::error::Error;
+use crate::gcs::GcsReader;
+use crate::http::Range;
+
+pub(crate) enum SourceReader {
+    Local(File),
+    Gcs(GcsReader),
+}
+
+fn is_remote(location: &str) -> bool {
+    location.starts_with("gs://") || location.starts_with("http://")
+        || location.starts_with("https://")
+}
+
+impl SourceReader {
+    pub(crate) fn open(location: &str) -> Result<SourceReader, Error> {
+        if is_remote(location) {
+            Ok(SourceReader::Gcs(GcsReader::connect(location)?))
+        } else {
+            Ok(SourceReader::Local(File::open(location)?))
+        }
+    }
+    pub(crate) fn open_range(location: &str, range: &Range) -> Result<SourceReader, Error> {
+        if is_remote(location) {
+            Ok(SourceReader::Gcs(GcsReader::connect_range(location, range)?))
+        } else {
+            let mut file = File::open(location)?;
+            file.seek(SeekFrom::Start(range.from.unwrap_or(0)))?;
+            Ok(SourceReader::Local(file))
+        }
+    }
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            SourceReader::Local(file) => { file.read(buf) }
+            SourceReader::Gcs(gcs_reader) => { gcs_reader.read(buf) }
+        }
+    }
+}
+
+impl Seek for SourceReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            SourceReader::Local(file) => { file.seek(pos) }
+            SourceReader::Gcs(gcs_reader) => { gcs_reader.seek(pos) }
+        }
+    }
+}
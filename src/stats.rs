@@ -35,4 +35,85 @@ impl Stats {
             mean_of_squares - mean * mean
         }).collect()
     }
-}
\ No newline at end of file
+}
+
+pub(crate) fn mean_and_std_dev(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|value| { (value - mean) * (value - mean) }).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+// Inverse of the standard normal cumulative distribution function (Acklam's algorithm).
+// Returns negative infinity for p = 0, infinity for p = 1 and NaN for p outside [0, 1].
+pub(crate) fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e+01, 2.209460984245205e+02, -2.759285104469687e+02,
+        1.38357751867269e+02, -3.066479806614716e+01, 2.506628277459239e+00];
+    const B: [f64; 5] = [-5.447609879822406e+01, 1.615858368580409e+02, -1.556989798598866e+02,
+        6.680131188771972e+01, -1.328068155288572e+01];
+    const C: [f64; 6] = [-7.784894002430293e-03, -3.223964580411365e-01, -2.400758277161838e+00,
+        -2.549732539343734e+00, 4.374664141464968e+00, 2.938163982698783e+00];
+    const D: [f64; 4] = [7.784695709041462e-03, 3.224671290700398e-01, 2.445134137142996e+00,
+        3.754408661907416e+00];
+    const P_LOW: f64 = 0.02425;
+    if p.is_nan() || !(0.0..=1.0).contains(&p) {
+        f64::NAN
+    } else if p == 0.0 {
+        f64::NEG_INFINITY
+    } else if p == 1.0 {
+        f64::INFINITY
+    } else if p < P_LOW {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) /
+            ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q /
+            (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -normal_quantile(1.0 - p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normal_quantile;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6 * expected.abs().max(1.0),
+                "Expected {}, but got {}.", expected, actual);
+    }
+
+    #[test]
+    fn normal_quantile_of_common_probabilities() {
+        assert_eq!(normal_quantile(0.5), 0.0);
+        assert_close(normal_quantile(0.975), 1.959963984540054);
+        assert_close(normal_quantile(0.025), -1.959963984540054);
+        assert_close(normal_quantile(0.8413447460685429), 1.0);
+        assert_close(normal_quantile(0.02425), -1.9729610513118845);
+    }
+
+    #[test]
+    fn normal_quantile_of_tails() {
+        assert_close(normal_quantile(1e-6), -4.753424308822899);
+        assert_close(normal_quantile(1.0 - 1e-6), 4.753424308822899);
+        assert_close(normal_quantile(1e-12), -7.034483825301132);
+    }
+
+    #[test]
+    fn normal_quantile_is_antisymmetric() {
+        for p in [1e-9, 1e-4, 0.01, 0.02425, 0.1, 0.3, 0.49] {
+            assert_close(normal_quantile(1.0 - p), -normal_quantile(p));
+        }
+    }
+
+    #[test]
+    fn normal_quantile_at_and_beyond_bounds() {
+        assert_eq!(normal_quantile(0.0), f64::NEG_INFINITY);
+        assert_eq!(normal_quantile(1.0), f64::INFINITY);
+        assert!(normal_quantile(-0.1).is_nan());
+        assert!(normal_quantile(1.1).is_nan());
+        assert!(normal_quantile(f64::NAN).is_nan());
+    }
+}
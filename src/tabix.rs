@@ -1,16 +1,14 @@
 use std::ops::RangeBounds;
 use std::time::{Duration, Instant};
-use noodles::{bgzf, vcf, core};
+use noodles::{bgzf, core};
 use crate::config::GcsTabixConfig;
 use crate::Error;
-use noodles::tabix::{Index, Reader};
-use crate::gcs::GcsReader;
-use noodles::csi::binning_index::BinningIndex;
-use noodles::vcf::Record;
+use crate::source::SourceReader;
+use crate::vcf_index::VcfIndex;
+use crate::record_reader::{RecordReader, VariantHeader};
 use crate::http::Range;
 use crate::records::{RecordPrinter, RecordProcessor};
 use crate::region_iter::RegionIterGen;
-use std::string::String;
 
 pub(crate) fn tabix(config: &GcsTabixConfig) -> Result<(), Error> {
     let mut record_processor = RecordPrinter::new();
@@ -22,10 +20,11 @@ pub(crate) fn tabix(config: &GcsTabixConfig) -> Result<(), Error> {
 pub(crate) fn process_region<P: RecordProcessor>(data_url: &str, index_url: &str,
                                                  region: &core::Region, record_processor: &mut P)
                                                  -> Result<usize, Error> {
-    let index = read_index(index_url)?;
+    let variant_header = read_variant_header(data_url)?;
+    let index = VcfIndex::read(index_url, &variant_header.header)?;
     let n_records =
-        if let Some((i_chrom, _)) = index.reference_sequence_names().get_full(region.name()) {
-            read_region(&index, data_url, i_chrom, region, record_processor)?
+        if let Some(i_chrom) = index.position(region.name()) {
+            read_region(&index, data_url, &variant_header, i_chrom, region, record_processor)?
         } else {
             0
         };
@@ -34,16 +33,18 @@ pub(crate) fn process_region<P: RecordProcessor>(data_url: &str, index_url: &str
 
 pub(crate) fn sample_regions<P: RecordProcessor>(data_url: &str, index_url: &str,
                                                  record_processor: &mut P,
-                                                 region_iter_gen: &RegionIterGen)
+                                                 region_iter_gen: &mut RegionIterGen)
                                                  -> Result<usize, Error> {
-    let index = read_index(index_url)?;
+    let variant_header = read_variant_header(data_url)?;
+    let index = VcfIndex::read(index_url, &variant_header.header)?;
     let mut n_records: usize = 0;
-    for (i_chrom, chrom) in index.reference_sequence_names().iter().enumerate() {
+    for (i_chrom, chrom) in index.names().iter().enumerate() {
         let region_iter = region_iter_gen.new_region_iter(chrom.clone());
         for region in region_iter {
             println!("Now reading region {}.", region);
             let n_records_new =
-                read_region(&index, data_url, i_chrom, &region, record_processor)?;
+                read_region(&index, data_url, &variant_header, i_chrom, &region,
+                            record_processor)?;
             println!("Read {} records from region {}.", n_records_new, region);
             n_records += n_records_new;
         }
@@ -51,17 +52,9 @@ pub(crate) fn sample_regions<P: RecordProcessor>(data_url: &str, index_url: &str
     Ok(n_records)
 }
 
-fn read_index(index_url: &str) -> Result<Index, Error> {
-    let mut index_reader = Reader::new(GcsReader::connect(index_url)?);
-    let index = index_reader.read_index()?;
-    Ok(index)
-}
-
-pub(crate) fn read_vcf_header(data_url: &str) -> Result<vcf::Header, Error> {
-    let mut data_reader =
-        vcf::Reader::new(bgzf::Reader::new(GcsReader::connect(data_url)?));
-    let vcf_header = data_reader.read_header()?.parse::<vcf::Header>()?;
-    Ok(vcf_header)
+pub(crate) fn read_variant_header(data_url: &str) -> Result<VariantHeader, Error> {
+    let (_, variant_header) = RecordReader::open(SourceReader::open(data_url)?)?;
+    Ok(variant_header)
 }
 
 struct StatusReporter {
@@ -97,7 +90,8 @@ impl StatusReporter {
     }
 }
 
-fn read_region<P: RecordProcessor>(index: &Index, data_url: &str, i_chrom: usize,
+fn read_region<P: RecordProcessor>(index: &VcfIndex, data_url: &str,
+                                   variant_header: &VariantHeader, i_chrom: usize,
                                    region: &core::Region, record_processor: &mut P)
                                    -> Result<usize, Error> {
     let chunks = index.query(i_chrom, region.interval())?;
@@ -109,19 +103,17 @@ fn read_region<P: RecordProcessor>(index: &Index, data_url: &str, i_chrom: usize
             Range::new(Some(chunk.start().compressed()),
                        Some(chunk.end().compressed() + PADDING));
         let mut bgzf_reader =
-            bgzf::Reader::new(GcsReader::connect_range(data_url, &range)?);
+            bgzf::Reader::new(SourceReader::open_range(data_url, &range)?);
         bgzf_reader.seek(chunk.start())?;
-        let mut vcf_reader = vcf::Reader::new(bgzf_reader);
+        let mut record_reader = RecordReader::new(bgzf_reader, variant_header);
         let mut status_reporter = StatusReporter::new();
         loop {
-            let mut record_buffer = String::new();
-            vcf_reader.read_record(&mut record_buffer)?;
-            if record_buffer.is_empty() {
-                println!("Empty record buffer -  end of input? break.");
-                break;
-            }
-            let record = match record_buffer.parse::<Record>() {
-                Ok(record) => { record }
+            let record = match record_reader.read_record(variant_header) {
+                Ok(Some(record)) => { record }
+                Ok(None) => {
+                    println!("Empty record buffer -  end of input? break.");
+                    break;
+                }
                 Err(error) => {
                     println!("We're assuming the chunk has ended: {}", error);
                     break;
@@ -140,7 +132,7 @@ fn read_region<P: RecordProcessor>(index: &Index, data_url: &str, i_chrom: usize
                     break;
                 }
             }
-            if vcf_reader.get_ref().virtual_position() >= chunk.end() {
+            if record_reader.virtual_position() >= chunk.end() {
                 println!("Reached end of chunk");
                 break;
             }
@@ -152,3 +144,116 @@ fn read_region<P: RecordProcessor>(index: &Index, data_url: &str, i_chrom: usize
     Ok(n_records)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use noodles::{bgzf, core, tabix};
+    use noodles::csi::BinningIndex;
+    use noodles::csi::index::reference_sequence::bin::Chunk;
+    use noodles::vcf::Record;
+    use crate::error::Error;
+    use crate::records::RecordProcessor;
+    use crate::test_util::temp_path;
+    use crate::vcf_index::tests::tabix_aux;
+    use super::process_region;
+
+    const HEADER: &str = "##fileformat=VCFv4.3
+##contig=<ID=chr1>
+##contig=<ID=chr2>
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\ts2
+";
+    const RECORDS: [(&str, usize); 8] = [
+        ("chr1", 100), ("chr1", 200), ("chr1", 300), ("chr1", 400), ("chr1", 500),
+        ("chr1", 70000), ("chr2", 100), ("chr2", 200),
+    ];
+
+    struct LocusCollector {
+        loci: Vec<(String, usize)>,
+    }
+
+    impl RecordProcessor for LocusCollector {
+        fn process_record(&mut self, record: &Record) -> Result<(), Error> {
+            self.loci.push((record.chromosome().to_string(), usize::from(record.position())));
+            Ok(())
+        }
+    }
+
+    // Writes a bgzipped VCF with a block per record and both a tabix and a CSI index for it.
+    fn write_indexed_vcf(name: &str) -> (String, String, String) {
+        let data = temp_path(&format!("{}.vcf.gz", name));
+        let mut writer = bgzf::Writer::new(std::fs::File::create(&data).unwrap());
+        writer.write_all(HEADER.as_bytes()).unwrap();
+        writer.flush().unwrap();
+        let mut indexer = tabix::Index::indexer();
+        indexer.set_header(tabix::index::header::Builder::vcf().build());
+        for (chrom, pos) in RECORDS {
+            let start = writer.virtual_position();
+            writeln!(writer, "{}\t{}\t.\tA\tG\t.\t.\t.\tGT\t0/1\t1/1", chrom, pos).unwrap();
+            writer.flush().unwrap();
+            let chunk = Chunk::new(start, writer.virtual_position());
+            let pos = core::Position::try_from(pos).unwrap();
+            indexer.add_record(chrom, pos, pos, chunk);
+        }
+        writer.finish().unwrap();
+        let index = indexer.build();
+        let tbi = format!("{}.tbi", data);
+        let mut tabix_writer = tabix::Writer::new(std::fs::File::create(&tbi).unwrap());
+        tabix_writer.write_index(&index).unwrap();
+        tabix_writer.try_finish().unwrap();
+        // The CSI writer of noodles writes chunk starts as ends, so the CSI is encoded here.
+        let mut bytes: Vec<u8> = b"CSI\x01".to_vec();
+        let aux = tabix_aux(&["chr1", "chr2"]);
+        for value in [14i32, 5, aux.len() as i32] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend(aux);
+        bytes.extend_from_slice(&(index.reference_sequences().len() as i32).to_le_bytes());
+        for reference_sequence in index.reference_sequences() {
+            bytes.extend_from_slice(&(reference_sequence.bins().len() as i32).to_le_bytes());
+            for bin in reference_sequence.bins() {
+                bytes.extend_from_slice(&bin.id().to_le_bytes());
+                bytes.extend_from_slice(&u64::from(bin.chunks()[0].start()).to_le_bytes());
+                bytes.extend_from_slice(&(bin.chunks().len() as i32).to_le_bytes());
+                for chunk in bin.chunks() {
+                    bytes.extend_from_slice(&u64::from(chunk.start()).to_le_bytes());
+                    bytes.extend_from_slice(&u64::from(chunk.end()).to_le_bytes());
+                }
+            }
+        }
+        let csi = format!("{}.csi", data);
+        let mut csi_writer = bgzf::Writer::new(std::fs::File::create(&csi).unwrap());
+        csi_writer.write_all(&bytes).unwrap();
+        csi_writer.finish().unwrap();
+        (data, tbi, csi)
+    }
+
+    fn loci_in(data: &str, index: &str, region: &str) -> Vec<(String, usize)> {
+        let region = region.parse::<core::Region>().unwrap();
+        let mut collector = LocusCollector { loci: Vec::new() };
+        let n_records = process_region(data, index, &region, &mut collector).unwrap();
+        assert_eq!(n_records, collector.loci.len());
+        collector.loci
+    }
+
+    fn loci(chrom: &str, positions: &[usize]) -> Vec<(String, usize)> {
+        positions.iter().map(|pos| { (String::from(chrom), *pos) }).collect()
+    }
+
+    #[test]
+    fn process_region_of_local_file() {
+        let (data, tbi, csi) = write_indexed_vcf("region");
+        for index in [&tbi, &csi] {
+            assert_eq!(loci_in(&data, index, "chr1:200-400"), loci("chr1", &[200, 300, 400]));
+            assert_eq!(loci_in(&data, index, "chr1:201-399"), loci("chr1", &[300]));
+            assert_eq!(loci_in(&data, index, "chr1:450-100000"), loci("chr1", &[500, 70000]));
+            assert_eq!(loci_in(&data, index, "chr1:60000-80000"), loci("chr1", &[70000]));
+            assert_eq!(loci_in(&data, index, "chr1:600-700"), loci("chr1", &[]));
+            assert_eq!(loci_in(&data, index, "chr2:150-250"), loci("chr2", &[200]));
+            assert_eq!(loci_in(&data, index, "chr3:1-1000"), loci("chr3", &[]));
+        }
+        for file in [data, tbi, csi] {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub(crate) fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("phenics-{}-{}", std::process::id(), name));
+    String::from(path.to_str().unwrap())
+}
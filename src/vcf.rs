@@ -1,37 +1,123 @@
 use crate::config::VcfConfig;
 use crate::error::Error;
-use crate::{phenotype, sim};
-use crate::read::{read_vcf_file, read_vcf_stdin};
+use std::sync::Mutex;
+use std::thread;
+use std::thread::ScopedJoinHandle;
+use crate::{chunks, effects, phenotype, sim};
+use crate::read::{process_vcf_file, read_sample_ids, read_vcf_chunks, read_vcf_stdin};
+use crate::seed;
+use crate::phenotype::Phenotype;
+use crate::records::{CausalPicker, Picks, SexInferrer};
+use crate::compensation::{Compensation, Sexes};
+use crate::sim::allele_sim::AlleleSimGen;
+use crate::sim::genotype_sim::DosageField;
+use crate::sim::Sim;
+use crate::chunks::Chunk;
 
 pub(crate) fn process_vcf(config: &VcfConfig) -> Result<(), Error> {
     println!("Loading phenotypes from {}", config.phenotype_file);
-    let phenotypes = phenotype::load::load(&config.phenotype_file)?;
+    let definitions = phenotype::load::load(&config.phenotype_file)?;
+    let phenotypes = &definitions.phenotypes;
+    let seed = seed::pick_seed(config.seed);
+    println!("Using seed {}", seed);
+    let picks = pick_causal(&config.inputs, phenotypes, seed)?;
+    let allele_sim_gen = AlleleSimGen::new(&definitions, seed, picks);
+    let mut compensation =
+        Compensation::new(&config.compensation, &config.escape_list, &config.sex_file)?;
+    if let Some(compensation) = &mut compensation {
+        infer_sexes(&config.inputs, compensation, config.dosage_field)?;
+    }
+    let compensation = compensation.as_ref();
+    let effects_writer = effects::create_maybe(&config.effects_out, phenotypes)?.map(Mutex::new);
     match &config.inputs {
         None => {
-            let sim = read_vcf_stdin(&phenotypes)?;
+            let sim =
+                read_vcf_stdin(&allele_sim_gen, effects_writer.as_ref(), compensation,
+                               config.dosage_field)?;
             sim::io::write(&sim, &config.output)?;
         }
         Some(inputs) => {
-            let mut inputs_iter = inputs.iter();
-            match inputs_iter.next() {
-                None => {
-                    return Err(Error::from("Need to specify at least one input file."));
-                }
-                Some(input) => {
-                    println!("Next reading {}", input);
-                    let mut sim_all = read_vcf_file(input, &phenotypes)?;
-                    println!("File: {}", sim_all.create_summary());
-                    for input in inputs_iter {
-                        println!("Next reading {}", input);
-                        let sim_input = read_vcf_file(input, &phenotypes)?;
-                        println!("File: {}", sim_input.create_summary());
-                        sim_all = sim_all.try_add(&sim_input)?;
-                        println!("All : {}", sim_all.create_summary());
-                    }
-                    sim::io::write(&sim_all, &config.output)?;
-                }
+            let first_input = inputs.first().ok_or_else(|| {
+                Error::from("Need to specify at least one input file.")
+            })?;
+            let sample_ids = read_sample_ids(first_input)?;
+            if let Some(compensation) = compensation {
+                compensation.report_uncompensated(&sample_ids)?;
+            }
+            let mut chunks: Vec<Chunk> = Vec::new();
+            for input in inputs {
+                chunks.append(&mut chunks::split(input, config.threads)?);
+            }
+            let n_workers = config.threads.min(chunks.len());
+            let picked = allele_sim_gen.picked();
+            let results: Vec<Result<Sim, Error>> = thread::scope(|scope| {
+                let workers: Vec<ScopedJoinHandle<Result<Sim, Error>>> =
+                    (0..n_workers).map(|i_worker| {
+                        let worker_chunks: Vec<&Chunk> =
+                            chunks.iter().skip(i_worker).step_by(n_workers).collect();
+                        let mut sim =
+                            Sim::new(sample_ids.clone(), phenotypes, seed, picked.clone());
+                        let allele_sim_gen = &allele_sim_gen;
+                        let effects_writer = effects_writer.as_ref();
+                        scope.spawn(move || {
+                            read_vcf_chunks(&worker_chunks, &mut sim, allele_sim_gen,
+                                            effects_writer, compensation, config.dosage_field)?;
+                            Ok(sim)
+                        })
+                    }).collect();
+                workers.into_iter().map(|worker| { worker.join().unwrap() }).collect()
+            });
+            let mut sim_all: Option<Sim> = None;
+            for sim in results {
+                let sim = sim?;
+                println!("Worker: {}", sim.create_summary());
+                sim_all = Some(match sim_all {
+                    None => { sim }
+                    Some(sim_all) => { sim_all.try_add(&sim)? }
+                });
+            }
+            if let Some(sim_all) = sim_all {
+                println!("All : {}", sim_all.create_summary());
+                sim::io::write(&sim_all, &config.output)?;
             }
         }
     }
+    let effects_writer =
+        effects_writer.map(|effects_writer| { effects_writer.into_inner().unwrap() });
+    effects::finish_maybe(effects_writer)?;
+    allele_sim_gen.report_unmatched();
+    allele_sim_gen.report_unpaired();
+    Ok(())
+}
+
+fn pick_causal(inputs: &Option<Vec<String>>, phenotypes: &[Phenotype], seed: u64)
+               -> Result<Picks, Error> {
+    let mut causal_picker = CausalPicker::new(phenotypes, seed);
+    if causal_picker.is_needed() {
+        let inputs = inputs.as_ref().ok_or_else(|| {
+            Error::from(format!("Picking {} needs a first pass, so cannot read from stdin.",
+                                causal_picker.what_is_picked()))
+        })?;
+        for input in inputs {
+            println!("Picking {} from {}", causal_picker.what_is_picked(), input);
+            process_vcf_file(input, &mut causal_picker)?;
+        }
+    }
+    Ok(causal_picker.into_picks())
+}
+
+fn infer_sexes(inputs: &Option<Vec<String>>, compensation: &mut Compensation,
+               dosage_field: DosageField) -> Result<(), Error> {
+    if compensation.sexes.is_none() {
+        let inputs = inputs.as_ref().ok_or_else(|| {
+            Error::from("Inferring sex needs a first pass, so cannot read from stdin.")
+        })?;
+        let mut sex_inferrer = SexInferrer::new(compensation, dosage_field);
+        for input in inputs {
+            println!("Inferring sex from {}", input);
+            process_vcf_file(input, &mut sex_inferrer)?;
+        }
+        compensation.sexes = Some(Sexes::ByIndex(sex_inferrer.into_sexes()));
+    }
     Ok(())
 }
@@ -0,0 +1,163 @@
+use std::io::Read;
+use noodles::{bgzf, core, csi, tabix};
+use noodles::csi::binning_index::{merge_chunks, BinningIndex};
+use noodles::csi::index::reference_sequence::bin::Chunk;
+use crate::error::Error;
+use crate::source::SourceReader;
+use noodles::vcf::Header;
+
+const BGZF_MAGIC: [u8; 4] = [31, 139, 8, 4];
+const TABIX_MAGIC: &[u8; 4] = b"TBI\x01";
+const CSI_MAGIC: &[u8; 4] = b"CSI\x01";
+const TABIX_HEADER_SIZE: usize = 28;
+
+enum IndexKind {
+    Tabix(tabix::Index),
+    Csi(csi::Index),
+}
+
+pub(crate) struct VcfIndex {
+    names: Vec<String>,
+    index: IndexKind,
+}
+
+impl VcfIndex {
+    pub(crate) fn read(index_url: &str, header: &Header) -> Result<VcfIndex, Error> {
+        let mut bytes: Vec<u8> = Vec::new();
+        SourceReader::open(index_url)?.read_to_end(&mut bytes)?;
+        if !bytes.starts_with(&BGZF_MAGIC) {
+            return Err(Error::from(format!("Index {} is not bgzipped.", index_url)));
+        }
+        let mut magic = [0u8; 4];
+        bgzf::Reader::new(bytes.as_slice()).read_exact(&mut magic)?;
+        if &magic == TABIX_MAGIC {
+            let index = tabix::Reader::new(bytes.as_slice()).read_index()?;
+            let names: Vec<String> = index.reference_sequence_names().iter().cloned().collect();
+            Ok(VcfIndex { names, index: IndexKind::Tabix(index) })
+        } else if &magic == CSI_MAGIC {
+            let index = csi::Reader::new(bytes.as_slice()).read_index()?;
+            let names = match names_from_aux(index.aux()) {
+                Some(names) => { names }
+                None => { header.contigs().keys().cloned().collect() }
+            };
+            Ok(VcfIndex { names, index: IndexKind::Csi(index) })
+        } else {
+            Err(Error::from(format!("{} is neither a tabix nor a CSI index.", index_url)))
+        }
+    }
+    pub(crate) fn names(&self) -> &[String] {
+        &self.names
+    }
+    pub(crate) fn position(&self, chrom: &str) -> Option<usize> {
+        self.names.iter().position(|name| { name == chrom })
+    }
+    pub(crate) fn query(&self, i_chrom: usize, interval: core::region::Interval)
+                        -> Result<Vec<Chunk>, Error> {
+        let chunks = match &self.index {
+            IndexKind::Tabix(index) => { index.query(i_chrom, interval)? }
+            IndexKind::Csi(index) => { merge_chunks(&index.query(i_chrom, interval)?) }
+        };
+        Ok(chunks)
+    }
+}
+
+fn names_from_aux(aux: &[u8]) -> Option<Vec<String>> {
+    if aux.len() < TABIX_HEADER_SIZE {
+        return None;
+    }
+    let names_len = i32::from_le_bytes([aux[24], aux[25], aux[26], aux[27]]) as usize;
+    let names_bytes = aux.get(TABIX_HEADER_SIZE..TABIX_HEADER_SIZE + names_len)?;
+    let names: Vec<String> =
+        names_bytes.split(|byte| { *byte == 0 }).filter(|name| { !name.is_empty() })
+            .map(|name| { String::from_utf8_lossy(name).to_string() }).collect();
+    Some(names)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+    use noodles::{bgzf, core, csi, tabix};
+    use noodles::csi::index::reference_sequence::bin::Chunk;
+    use noodles::vcf::Header;
+    use super::VcfIndex;
+    use crate::test_util::temp_path;
+
+    const HEADER: &str = "##fileformat=VCFv4.3
+##contig=<ID=chrA>
+##contig=<ID=chrB>
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO
+";
+
+    fn write_tabix(file: &str) -> Chunk {
+        let chunk = Chunk::new(bgzf::VirtualPosition::from(144),
+                               bgzf::VirtualPosition::from(233));
+        let mut indexer = tabix::Index::indexer();
+        indexer.set_header(tabix::index::header::Builder::vcf().build());
+        let start = core::Position::try_from(8).unwrap();
+        let end = core::Position::try_from(13).unwrap();
+        indexer.add_record("chr7", start, end, chunk);
+        let mut writer = tabix::Writer::new(std::fs::File::create(file).unwrap());
+        writer.write_index(&indexer.build()).unwrap();
+        writer.try_finish().unwrap();
+        chunk
+    }
+
+    fn write_csi(file: &str, aux: Vec<u8>) {
+        let index = csi::Index::builder().set_aux(aux).build();
+        let mut writer = csi::Writer::new(std::fs::File::create(file).unwrap());
+        writer.write_index(&index).unwrap();
+    }
+
+    pub(crate) fn tabix_aux(names: &[&str]) -> Vec<u8> {
+        let names: Vec<u8> =
+            names.iter().flat_map(|name| { name.bytes().chain([0u8]) }).collect();
+        let mut aux: Vec<u8> = Vec::new();
+        for value in [2i32, 1, 2, 0, b'#' as i32, 0, names.len() as i32] {
+            aux.extend_from_slice(&value.to_le_bytes());
+        }
+        aux.extend(names);
+        aux
+    }
+
+    #[test]
+    fn read_tabix_index() {
+        let header: Header = HEADER.parse().unwrap();
+        let file = temp_path("index.tbi");
+        let chunk = write_tabix(&file);
+        let index = VcfIndex::read(&file, &header).unwrap();
+        assert_eq!(index.names(), &[String::from("chr7")]);
+        assert_eq!(index.position("chr7"), Some(0));
+        let start = core::Position::try_from(1).unwrap();
+        let end = core::Position::try_from(100).unwrap();
+        let chunks = index.query(0, core::region::Interval::from(start..=end)).unwrap();
+        assert_eq!(chunks, vec![chunk]);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn read_csi_index() {
+        let header: Header = HEADER.parse().unwrap();
+        let file = temp_path("index.csi");
+        write_csi(&file, tabix_aux(&["chr3", "chr4"]));
+        let index = VcfIndex::read(&file, &header).unwrap();
+        assert_eq!(index.names(), &[String::from("chr3"), String::from("chr4")]);
+        write_csi(&file, Vec::new());
+        let index = VcfIndex::read(&file, &header).unwrap();
+        assert_eq!(index.names(), &[String::from("chrA"), String::from("chrB")]);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn read_index_that_is_not_an_index() {
+        let header: Header = HEADER.parse().unwrap();
+        let file = temp_path("not-index.gz");
+        std::fs::write(&file, HEADER).unwrap();
+        assert!(VcfIndex::read(&file, &header).is_err());
+        let mut writer = bgzf::Writer::new(std::fs::File::create(&file).unwrap());
+        writer.write_all(HEADER.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        let error = VcfIndex::read(&file, &header).err().unwrap();
+        assert!(error.to_string().contains("neither a tabix nor a CSI index"));
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use noodles::{bcf, bgzf, vcf};
+
+const PHENOTYPES: &str = "foo=norm(0,1),0.3\nbar=causal(3,norm(0,1)),0.5,model=dom\n";
+
+fn data_file(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("data").join(name)
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("phenics-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn bgzip(source: &Path, target: &Path) {
+    let mut writer = bgzf::Writer::new(fs::File::create(target).unwrap());
+    writer.write_all(&fs::read(source).unwrap()).unwrap();
+    writer.finish().unwrap();
+}
+
+fn run_vcf(dir: &Path, input: &Path, name: &str, threads: usize) -> (Output, String, String) {
+    let phenotype_file = dir.join("phenotypes.txt");
+    fs::write(&phenotype_file, PHENOTYPES).unwrap();
+    let output_file = dir.join(format!("{}.tsv", name));
+    let effects_file = dir.join(format!("{}.effects.tsv", name));
+    let output =
+        Command::new(env!("CARGO_BIN_EXE_phenics"))
+            .arg("vcf").arg("-i").arg(input).arg("-p").arg(&phenotype_file)
+            .arg("-s").arg("42").arg("-t").arg(threads.to_string())
+            .arg("-o").arg(&output_file).arg("-e").arg(&effects_file)
+            .output().unwrap();
+    let liabilities = fs::read_to_string(&output_file).unwrap_or_default();
+    let effects = fs::read_to_string(&effects_file).unwrap_or_default();
+    (output, liabilities, effects)
+}
+
+#[test]
+fn bcf_gives_same_output_as_vcf() {
+    let dir = temp_dir("bcf-same");
+    let vcf_file = dir.join("small.vcf.gz");
+    bgzip(&data_file("small.vcf"), &vcf_file);
+    let (_, vcf_liabilities, vcf_effects) = run_vcf(&dir, &vcf_file, "vcf", 1);
+    assert!(vcf_liabilities.contains("\ns0\t"));
+    assert!(vcf_effects.lines().count() > 1);
+    for threads in [1, 3] {
+        let (_, bcf_liabilities, bcf_effects) =
+            run_vcf(&dir, &data_file("small.bcf"), "bcf", threads);
+        assert_eq!(bcf_liabilities, vcf_liabilities);
+        assert_eq!(bcf_effects, vcf_effects);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bcf_with_string_genotypes_is_an_error() {
+    let dir = temp_dir("bcf-string-gt");
+    let mut vcf_reader =
+        vcf::Reader::new(BufReader::new(fs::File::open(data_file("small.vcf")).unwrap()));
+    let raw_header = vcf_reader.read_header().unwrap();
+    let header: vcf::Header = raw_header.parse().unwrap();
+    let string_maps: bcf::header::StringMaps = raw_header.parse().unwrap();
+    let bcf_file = dir.join("string-gt.bcf");
+    let mut bcf_writer = bcf::Writer::new(fs::File::create(&bcf_file).unwrap());
+    bcf_writer.write_file_format().unwrap();
+    bcf_writer.write_header(&header).unwrap();
+    for record in vcf_reader.records(&header) {
+        bcf_writer.write_vcf_record(&header, &string_maps, &record.unwrap()).unwrap();
+    }
+    bcf_writer.try_finish().unwrap();
+    drop(bcf_writer);
+    let (output, _, _) = run_vcf(&dir, &bcf_file, "string-gt", 1);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Cannot decode BCF record"), "{}", stderr);
+    assert!(!stderr.contains("Done!"), "{}", stderr);
+    fs::remove_dir_all(&dir).unwrap();
+}